@@ -0,0 +1,160 @@
+use chrono::NaiveDate;
+
+use crate::{config::Config, metrics::{DayData, MetricRegistry}, sorted_dates, weeks, Analysis};
+
+/// Metric values of one day, in the order of the metric registry.
+#[derive(Debug, Clone)]
+pub struct DayValues {
+    /// One-based day of the recording, as in the Day column.
+    pub number: usize,
+    pub date: NaiveDate,
+    /// `None` where the metric is undefined on the day.
+    pub values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PeriodKind {
+    Week,
+    Weekend,
+    Weekdays,
+}
+
+impl PeriodKind {
+    /// Identifier used in the exports.
+    pub fn id(&self) -> &'static str {
+        match self {
+            PeriodKind::Week => "week",
+            PeriodKind::Weekend => "weekend",
+            PeriodKind::Weekdays => "weekdays",
+        }
+    }
+}
+
+/// Daily values averaged over a calendar week, or only over its weekend or
+/// weekdays.
+#[derive(Debug, Clone)]
+pub struct PeriodValues {
+    pub kind: PeriodKind,
+    /// One-based calendar week of the recording.
+    pub number: usize,
+    pub days: Vec<DayValues>,
+    /// Averages of the days the metric is defined on.
+    pub values: Vec<Option<f64>>,
+}
+
+/// The daily and weekly values every report is written from.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub days: Vec<DayValues>,
+    pub periods: Vec<PeriodValues>,
+}
+
+impl Summary {
+    pub fn compute(analysis: &Analysis, metrics: &MetricRegistry, config: &Config) -> Self {
+        let days = daily(analysis, metrics, config);
+        let periods = weekly(&days, config);
+        Self { days, periods }
+    }
+}
+
+pub fn daily(analysis: &Analysis, metrics: &MetricRegistry, config: &Config) -> Vec<DayValues> {
+    sorted_dates(&analysis.sensor_data)
+        .into_iter()
+        .enumerate()
+        .map(|(index, date)| {
+            let day = DayData {
+                entries: analysis.sensor_data.get(&date).map(|e| e.as_slice()).unwrap_or_default(),
+                excluded: analysis.excluded_data.get(&date).map(|e| e.as_slice()).unwrap_or_default(),
+            };
+            DayValues {
+                number: index + 1,
+                date,
+                values: metrics.metrics().iter().map(|m| m.compute(&day, config)).collect(),
+            }
+        })
+        .collect()
+}
+
+/// All the weeks first, then their weekends and then their weekdays. Periods
+/// without any days are left out.
+pub fn weekly(days: &[DayValues], config: &Config) -> Vec<PeriodValues> {
+    let dates: Vec<NaiveDate> = days.iter().map(|d| d.date).collect();
+    let weeks = weeks::group_by_week(&dates, config.week_start);
+
+    let mut periods = vec![];
+    for kind in [PeriodKind::Week, PeriodKind::Weekend, PeriodKind::Weekdays] {
+        for (index, week) in weeks.iter().enumerate() {
+            let included: Vec<DayValues> = days
+                .iter()
+                .filter(|d| week.contains(&d.date))
+                .filter(|d| match kind {
+                    PeriodKind::Week => true,
+                    PeriodKind::Weekend => weeks::is_weekend(&d.date),
+                    PeriodKind::Weekdays => !weeks::is_weekend(&d.date),
+                })
+                .cloned()
+                .collect();
+            if included.is_empty() {
+                continue;
+            }
+            periods.push(PeriodValues {
+                kind,
+                number: index + 1,
+                values: average(&included),
+                days: included,
+            });
+        }
+    }
+    periods
+}
+
+/// Average of one metric over the days matching `filter`, `None` if no day
+/// does or the metric is undefined on all of them.
+pub fn mean(days: &[DayValues], index: usize, filter: impl Fn(&DayValues) -> bool) -> Option<f64> {
+    let values: Vec<f64> = days.iter().filter(|d| filter(d)).filter_map(|d| d.values[index]).collect();
+    match values.is_empty() {
+        true => None,
+        false => Some(values.iter().sum::<f64>() / values.len() as f64),
+    }
+}
+
+fn average(days: &[DayValues]) -> Vec<Option<f64>> {
+    (0..days[0].values.len()).map(|i| mean(days, i, |_| true)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn weeks_average_their_days_and_split_into_weekends_and_weekdays() {
+        let config = testing::config("[metrics]\nenabled=tot_counts, ave_counts_epoch");
+        let metrics = MetricRegistry::from_config(&config).unwrap();
+        let mut entries = testing::entries("2023-05-05 10:00", &[10, 20]);
+        entries.extend(testing::entries("2023-05-06 10:00", &[-1, -1]));
+        entries.extend(testing::entries("2023-05-07 10:00", &[40]));
+        entries.extend(testing::entries("2023-05-08 10:00", &[600]));
+        let summary = Summary::compute(&testing::analysis(entries), &metrics, &config);
+
+        let periods: Vec<(&str, usize, usize, &[Option<f64>])> =
+            summary.periods.iter().map(|p| (p.kind.id(), p.number, p.days.len(), p.values.as_slice())).collect();
+        assert_eq!(periods, [
+            ("week", 1, 3, &[Some(70. / 3.), Some(27.5)][..]),
+            ("week", 2, 1, &[Some(600.), Some(600.)][..]),
+            ("weekend", 1, 2, &[Some(20.), Some(40.)][..]),
+            ("weekdays", 1, 1, &[Some(30.), Some(15.)][..]),
+            ("weekdays", 2, 1, &[Some(600.), Some(600.)][..]),
+        ]);
+    }
+
+    #[test]
+    fn means_skip_undefined_days() {
+        let day = |number, value| DayValues { number, date: NaiveDate::default(), values: vec![value] };
+        let days = [day(1, Some(2.)), day(2, None), day(3, Some(4.))];
+
+        assert_eq!(mean(&days, 0, |_| true), Some(3.));
+        assert_eq!(mean(&days, 0, |d| d.number == 2), None);
+        assert_eq!(mean(&days, 0, |d| d.number > 3), None);
+    }
+}
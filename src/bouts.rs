@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use crate::{config::Config, intensity, SensorEntry};
+
+/// A run of activity in the `[bouts] band` or above, moderate by default. Up to
+/// `[bouts] tolerance_epochs` consecutive epochs below the band are allowed
+/// inside a bout.
+#[derive(Debug, Clone)]
+pub struct Bout {
+    pub start: NaiveDateTime,
+    /// Exclusive, the end of the last active epoch.
+    pub end: NaiveDateTime,
+    pub epochs: usize,
+    /// Epochs in every band of `[bands]`, from the lowest.
+    pub band_epochs: Vec<usize>,
+    pub total_counts: i64,
+}
+
+/// Finds every bout lasting at least `[bouts] min_minutes`. Bouts end at gaps in
+/// the recording, including excluded intervals, and at non-wear epochs.
+pub fn detect(sensor_data: &HashMap<NaiveDate, Vec<SensorEntry>>, config: &Config) -> Vec<Bout> {
+    let mut entries: Vec<&SensorEntry> = sensor_data.values().flatten().collect();
+    entries.sort_by_key(|e| e.timestamp());
+
+    let epoch = Duration::seconds(config.epoch_seconds.into());
+    let min_epochs = (config.bout_min_minutes * 60.0 / config.epoch_seconds as f64).ceil().max(1.) as usize;
+    let cutpoint = config.bands[config.bout_band].cutpoint;
+
+    let mut bouts = vec![];
+    // first and last active epoch of the current bout
+    let mut current: Option<(usize, usize)> = None;
+    let mut misses = 0;
+
+    for (i, entry) in entries.iter().enumerate() {
+        let contiguous = i == 0 || entry.timestamp() - entries[i - 1].timestamp() == epoch;
+        if !contiguous || entry.value < 0 {
+            if let Some((first, last)) = current.take() {
+                push_bout(&mut bouts, &entries[first..=last], min_epochs, epoch, config);
+            }
+            if entry.value < 0 {
+                continue;
+            }
+        }
+
+        if entry.value >= cutpoint {
+            current = Some((current.map(|(first, _)| first).unwrap_or(i), i));
+            misses = 0;
+        } else if let Some((first, last)) = current {
+            misses += 1;
+            if misses > config.bout_tolerance_epochs {
+                push_bout(&mut bouts, &entries[first..=last], min_epochs, epoch, config);
+                current = None;
+            }
+        }
+    }
+    if let Some((first, last)) = current {
+        push_bout(&mut bouts, &entries[first..=last], min_epochs, epoch, config);
+    }
+    bouts
+}
+
+fn push_bout(bouts: &mut Vec<Bout>, entries: &[&SensorEntry], min_epochs: usize, epoch: Duration, config: &Config) {
+    if entries.len() < min_epochs {
+        return;
+    }
+    bouts.push(Bout {
+        start: entries[0].timestamp(),
+        end: entries[entries.len() - 1].timestamp() + epoch,
+        epochs: entries.len(),
+        band_epochs: (0..config.bands.len())
+            .map(|band| entries.iter().filter(|e| intensity::band_index(e.value, &config.bands) == Some(band)).count())
+            .collect(),
+        total_counts: entries.iter().map(|e| e.value as i64).sum(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn lengths(bouts: &[Bout]) -> Vec<usize> {
+        bouts.iter().map(|b| b.epochs).collect()
+    }
+
+    #[test]
+    fn bouts_allow_short_drops() {
+        let values = [600, 700, 1200, 100, 800, 900, 0, 0, 600];
+        let sensor_data = testing::by_day(testing::entries("2023-05-03 10:00", &values));
+
+        let bouts = detect(&sensor_data, &testing::config("[bouts]\nmin_minutes=1\ntolerance_epochs=1"));
+        assert_eq!(lengths(&bouts), [6]);
+        assert_eq!(bouts[0].band_epochs, [0, 1, 4, 1]);
+        assert_eq!(bouts[0].total_counts, 4300);
+        assert_eq!(bouts[0].end - bouts[0].start, Duration::seconds(90));
+
+        let bouts = detect(&sensor_data, &testing::config("[bouts]\nmin_minutes=0.5\ntolerance_epochs=0"));
+        assert_eq!(lengths(&bouts), [3, 2]);
+    }
+
+    #[test]
+    fn bouts_end_at_non_wear_and_gaps() {
+        let mut entries = testing::entries("2023-05-03 10:00", &[600, 600, -1, 600, 600, 600, 600]);
+        entries.remove(5);
+        let sensor_data = testing::by_day(entries);
+
+        let bouts = detect(&sensor_data, &testing::config("[bouts]\nmin_minutes=0.25\ntolerance_epochs=5"));
+        assert_eq!(lengths(&bouts), [2, 2, 1]);
+    }
+
+    #[test]
+    fn bouts_start_at_the_configured_band() {
+        let values = [100, 100, 600, 100, 0, 0];
+        let sensor_data = testing::by_day(testing::entries("2023-05-03 10:00", &values));
+
+        let bouts = detect(&sensor_data, &testing::config("[bouts]\nmin_minutes=0.5\nband=low"));
+        assert_eq!(lengths(&bouts), [4]);
+        assert!(detect(&sensor_data, &testing::config("[bouts]\nmin_minutes=0.5")).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use std::error::Error;
+
+use chrono::Timelike;
+use rust_xlsxwriter::{Chart, ChartType, Format, Workbook, Worksheet};
+
+use crate::{aggregate::{self, Summary}, columns::ColumnRegistry, i18n::Translations, metrics::MetricRegistry, weeks, Analysis};
+
+/// Rows between the top left corners of the charts, a chart is about 15 rows high.
+const CHART_ROWS: u32 = 16;
+const CHART_COLUMN: u16 = 7;
+
+/// Charts of the Daily data on their own sheet. The weekday/weekend comparison
+/// and the hourly profile aren't on any other sheet, so their values are
+/// written next to the charts.
+pub fn write_charts_sheet(
+    workbook: &mut Workbook,
+    analysis: &Analysis,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    registry: &ColumnRegistry,
+    translations: &Translations,
+    bold_format: &Format,
+) -> Result<(), Box<dyn Error>> {
+    let daily_sheet = translations.get("sheet.daily");
+    let last_day = summary.days.len() as u32;
+    let date_column = registry.daily_position("date").unwrap_or_default();
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.charts"))?;
+    let mut chart_row = 0;
+
+    // stacked intensity time per day, straight from the Daily columns
+    // from the most intense band
+    let intensity_columns: Vec<(&str, u16)> = metrics
+        .band_positions()
+        .into_iter()
+        .filter_map(|index| {
+            let id = metrics.metrics()[index].id();
+            Some((id, registry.daily_position(id)?))
+        })
+        .collect();
+    if !intensity_columns.is_empty() && last_day > 0 {
+        let mut chart = Chart::new(ChartType::ColumnStacked);
+        for (id, column) in intensity_columns.iter() {
+            chart
+                .add_series()
+                .set_name(registry.header(id))
+                .set_categories((daily_sheet, 1, date_column, last_day, date_column))
+                .set_values((daily_sheet, 1, *column, last_day, *column));
+        }
+        chart.title().set_name(translations.get("chart.intensity"));
+        chart.x_axis().set_name(translations.get("axis.date"));
+        chart.y_axis().set_name(translations.get("axis.duration")).set_num_format("[h]:mm");
+        sheet.insert_chart(chart_row, CHART_COLUMN, &chart)?;
+        chart_row += CHART_ROWS;
+    }
+
+    if let (Some(column), true) = (registry.daily_position("tot_counts"), last_day > 0) {
+        let mut chart = Chart::new(ChartType::Column);
+        chart
+            .add_series()
+            .set_name(registry.header("tot_counts"))
+            .set_categories((daily_sheet, 1, date_column, last_day, date_column))
+            .set_values((daily_sheet, 1, column, last_day, column));
+        chart.title().set_name(translations.get("chart.counts"));
+        chart.x_axis().set_name(translations.get("axis.date"));
+        chart.y_axis().set_name(translations.get("axis.counts"));
+        chart.legend().set_hidden();
+        sheet.insert_chart(chart_row, CHART_COLUMN, &chart)?;
+        chart_row += CHART_ROWS;
+    }
+
+    let sheet_name = translations.get("sheet.charts").to_string();
+    if write_weekday_weekend(sheet, &sheet_name, summary, metrics, translations, bold_format, chart_row)? {
+        chart_row += CHART_ROWS;
+    }
+    write_hourly_profile(sheet, &sheet_name, analysis, translations, bold_format, chart_row)?;
+    Ok(())
+}
+
+/// Average intensity minutes of weekdays and weekend days, in columns D to F.
+fn write_weekday_weekend(
+    sheet: &mut Worksheet,
+    sheet_name: &str,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    translations: &Translations,
+    bold_format: &Format,
+    chart_row: u32,
+) -> Result<bool, Box<dyn Error>> {
+    let intensities = metrics.band_positions();
+    if intensities.is_empty() {
+        return Ok(false);
+    }
+
+    let groups = [
+        (translations.get("label.weekdays"), false),
+        (translations.get("label.weekend"), true),
+    ];
+    sheet.set_column_width(3, 14)?;
+    for (i, (label, _)) in groups.iter().enumerate() {
+        sheet.write_with_format(0, 4 + i as u16, *label, bold_format)?;
+    }
+    for (row, index) in intensities.iter().enumerate() {
+        let row = row as u32 + 1;
+        let metric = &metrics.metrics()[*index];
+        sheet.write(row, 3, translations.column(metric.id(), metric.name()))?;
+        for (i, (_, weekend)) in groups.iter().enumerate() {
+            if let Some(mean) = aggregate::mean(&summary.days, *index, |d| weeks::is_weekend(&d.date) == *weekend) {
+                sheet.write(row, 4 + i as u16, mean / 60.)?;
+            }
+        }
+    }
+
+    let last_row = intensities.len() as u32;
+    let mut chart = Chart::new(ChartType::Column);
+    for (i, (label, _)) in groups.iter().enumerate() {
+        let column = 4 + i as u16;
+        chart
+            .add_series()
+            .set_name(*label)
+            .set_categories((sheet_name, 1, 3, last_row, 3))
+            .set_values((sheet_name, 1, column, last_row, column));
+    }
+    chart.title().set_name(translations.get("chart.weekday_weekend"));
+    chart.y_axis().set_name(translations.get("axis.minutes"));
+    sheet.insert_chart(chart_row, CHART_COLUMN, &chart)?;
+    Ok(true)
+}
+
+/// Mean counts per epoch in every hour of the day over all analysed days,
+/// leaving out non-wear epochs, in columns A and B.
+fn write_hourly_profile(
+    sheet: &mut Worksheet,
+    sheet_name: &str,
+    analysis: &Analysis,
+    translations: &Translations,
+    bold_format: &Format,
+    chart_row: u32,
+) -> Result<(), Box<dyn Error>> {
+    let mut totals = [(0i64, 0i64); 24];
+    for entry in analysis.sensor_data.values().flatten().filter(|e| e.value >= 0) {
+        let hour = &mut totals[entry.time.hour() as usize];
+        hour.0 += entry.value as i64;
+        hour.1 += 1;
+    }
+
+    sheet.write_with_format(0, 0, translations.get("axis.hour"), bold_format)?;
+    sheet.write_with_format(0, 1, translations.get("axis.counts_epoch"), bold_format)?;
+    sheet.set_column_width(1, 14)?;
+    for (hour, (counts, epochs)) in totals.iter().enumerate() {
+        let row = hour as u32 + 1;
+        sheet.write(row, 0, hour as u32)?;
+        if *epochs > 0 {
+            sheet.write(row, 1, *counts as f64 / *epochs as f64)?;
+        }
+    }
+
+    let mut chart = Chart::new(ChartType::Line);
+    chart
+        .add_series()
+        .set_name(translations.get("axis.counts_epoch"))
+        .set_categories((sheet_name, 1, 0, 24, 0))
+        .set_values((sheet_name, 1, 1, 24, 1));
+    chart.title().set_name(translations.get("chart.hourly"));
+    chart.x_axis().set_name(translations.get("axis.hour"));
+    chart.y_axis().set_name(translations.get("axis.counts_epoch"));
+    chart.legend().set_hidden();
+    sheet.insert_chart(chart_row, CHART_COLUMN, &chart)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use calamine::{open_workbook, DataType, Reader, Xlsx};
+
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn charts_sheet_has_the_weekday_weekend_and_hourly_values() {
+        let config = testing::config("");
+        let metrics = MetricRegistry::from_config(&config).unwrap();
+        let translations = Translations::from_config(&config).unwrap();
+        let registry = ColumnRegistry::from_config(&metrics, &translations, &config).unwrap();
+        let mut entries = testing::entries("2023-05-05 10:59:30", &[600, 600, 10, -1]);
+        entries.extend(testing::entries("2023-05-06 10:00", &[1200, 20]));
+        let analysis = testing::analysis(entries);
+        let summary = Summary::compute(&analysis, &metrics, &config);
+
+        let path = std::env::temp_dir().join("charts_sheet_has_the_weekday_weekend_and_hourly_values.xlsx");
+        let mut workbook = Workbook::new();
+        workbook.add_worksheet().set_name(registry.daily_sheet()).unwrap();
+        write_charts_sheet(&mut workbook, &analysis, &summary, &metrics, &registry, &translations, &Format::new()).unwrap();
+        workbook.save(&path).unwrap();
+
+        let mut workbook: Xlsx<_> = open_workbook(&path).unwrap();
+        let range = workbook.worksheet_range(translations.get("sheet.charts")).unwrap().unwrap();
+        let value = |row: u32, column: u32| range.get_value((row, column)).cloned();
+        // hours 10 and 11, the non-wear epoch left out
+        assert_eq!(value(11, 1), Some(DataType::Float(2420. / 4.)));
+        assert_eq!(value(12, 1), Some(DataType::Float(10.)));
+        assert_eq!(value(13, 1), Some(DataType::Empty));
+        // vigorous and moderate minutes on weekdays and the weekend
+        assert_eq!(value(1, 4), Some(DataType::Float(0.)));
+        assert_eq!(value(1, 5), Some(DataType::Float(0.25)));
+        assert_eq!(value(2, 4), Some(DataType::Float(0.5)));
+    }
+}
@@ -0,0 +1,231 @@
+use std::{collections::HashMap, error::Error};
+
+use chrono::{Duration, NaiveDate, NaiveTime, Timelike};
+use rust_xlsxwriter::{ExcelDateTime, Format, FormatBorder, Workbook};
+
+use crate::{config::Config, i18n::Translations, stats, Analysis};
+
+const MINUTES_PER_DAY: usize = 24 * 60;
+const L5_MINUTES: usize = 5 * 60;
+const M10_MINUTES: usize = 10 * 60;
+
+const COLUMNS: [&str; 9] = ["label", "hours", "is", "iv", "ra", "l5", "l5_onset", "m10", "m10_onset"];
+
+/// Non-parametric rhythm of a recording, from the worn epochs only.
+#[derive(Debug, Clone, Default)]
+pub struct Rhythm {
+    /// Clock hours with at least one worn epoch.
+    pub hours: usize,
+    /// Interdaily stability, 0 to 1.
+    pub is: Option<f64>,
+    /// Intradaily variability, 0 to about 2.
+    pub iv: Option<f64>,
+    /// Relative amplitude of M10 and L5, 0 to 1.
+    pub ra: Option<f64>,
+    /// Least active 5 hours of the average day.
+    pub l5: Option<Level>,
+    /// Most active 10 hours of the average day.
+    pub m10: Option<Level>,
+}
+
+/// Mean counts per minute of a stretch of the average day and when it starts.
+#[derive(Debug, Clone, Copy)]
+pub struct Level {
+    pub counts: f64,
+    pub onset: NaiveTime,
+}
+
+impl Rhythm {
+    pub fn compute(analysis: &Analysis, config: &Config) -> Self {
+        let per_minute = 60. / config.epoch_seconds as f64;
+        let mut profile = vec![(0., 0); MINUTES_PER_DAY];
+        for entry in analysis.sensor_data.values().flatten().filter(|e| e.value >= 0) {
+            let (sum, epochs) = &mut profile[(entry.time.hour() * 60 + entry.time.minute()) as usize];
+            *sum += entry.value as f64 * per_minute;
+            *epochs += 1;
+        }
+        let hours = hourly(analysis, config);
+
+        let profile: Vec<Option<f64>> = profile
+            .iter()
+            .map(|(sum, epochs)| (*epochs > 0).then(|| sum / *epochs as f64))
+            .collect();
+        let l5 = level(&profile, L5_MINUTES, |a, b| a < b);
+        let m10 = level(&profile, M10_MINUTES, |a, b| a > b);
+        let ra = match (l5, m10) {
+            (Some(l5), Some(m10)) if l5.counts + m10.counts > 0. => Some((m10.counts - l5.counts) / (m10.counts + l5.counts)),
+            _ => None,
+        };
+        Self {
+            hours: hours.iter().flatten().count(),
+            is: interdaily_stability(&hours),
+            iv: intradaily_variability(&hours),
+            ra,
+            l5,
+            m10,
+        }
+    }
+}
+
+/// Mean counts per minute of every clock hour from midnight of the first worn
+/// day to the end of the last one, none for hours without worn epochs so gaps
+/// break the series.
+pub fn hourly(analysis: &Analysis, config: &Config) -> Vec<Option<f64>> {
+    let per_minute = 60. / config.epoch_seconds as f64;
+    let mut hourly: HashMap<(NaiveDate, u32), (f64, usize)> = HashMap::new();
+    for entry in analysis.sensor_data.values().flatten().filter(|e| e.value >= 0) {
+        let (sum, epochs) = hourly.entry((entry.date, entry.time.hour())).or_default();
+        *sum += entry.value as f64 * per_minute;
+        *epochs += 1;
+    }
+
+    let mut dates: Vec<NaiveDate> = hourly.keys().map(|(date, _)| *date).collect();
+    dates.sort();
+    let mut hours = vec![];
+    if let (Some(first), Some(last)) = (dates.first(), dates.last()) {
+        let mut date = *first;
+        while date <= *last {
+            for hour in 0..24 {
+                hours.push(hourly.get(&(date, hour)).map(|(sum, epochs)| sum / *epochs as f64));
+            }
+            date += Duration::days(1);
+        }
+    }
+    hours
+}
+
+/// Share of the hourly variance explained by the hour of the day. Hours are
+/// weighted by how many days cover them, which is the usual formula when no
+/// hour is missing.
+fn interdaily_stability(hours: &[Option<f64>]) -> Option<f64> {
+    let values: Vec<f64> = hours.iter().flatten().copied().collect();
+    let mean = stats::mean(&values)?;
+    let total: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    if total == 0. {
+        return None;
+    }
+    let mut by_hour = [(0., 0); 24];
+    for (i, value) in hours.iter().enumerate() {
+        if let Some(value) = value {
+            by_hour[i % 24].0 += value;
+            by_hour[i % 24].1 += 1;
+        }
+    }
+    let between: f64 = by_hour
+        .iter()
+        .filter(|(_, n)| *n > 0)
+        .map(|(sum, n)| *n as f64 * (sum / *n as f64 - mean).powi(2))
+        .sum();
+    Some(between / total)
+}
+
+/// Mean squared change between consecutive hours over the variance, only
+/// counting changes where both hours were worn.
+fn intradaily_variability(hours: &[Option<f64>]) -> Option<f64> {
+    let values: Vec<f64> = hours.iter().flatten().copied().collect();
+    let mean = stats::mean(&values)?;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    let changes: Vec<f64> = hours
+        .windows(2)
+        .filter_map(|pair| Some((pair[1]? - pair[0]?).powi(2)))
+        .collect();
+    match (stats::mean(&changes), variance == 0.) {
+        (Some(change), false) => Some(change / variance),
+        _ => None,
+    }
+}
+
+/// The stretch of `minutes` of the average day, wrapping over midnight, whose
+/// mean is `better` than that of every other one.
+fn level(profile: &[Option<f64>], minutes: usize, better: fn(f64, f64) -> bool) -> Option<Level> {
+    let mut best: Option<(usize, f64)> = None;
+    for start in 0..profile.len() {
+        let values: Vec<f64> = (start..start + minutes).filter_map(|m| profile[m % profile.len()]).collect();
+        if let Some(mean) = stats::mean(&values) {
+            if best.is_none_or(|(_, b)| better(mean, b)) {
+                best = Some((start, mean));
+            }
+        }
+    }
+    let (start, counts) = best?;
+    Some(Level {
+        counts,
+        onset: NaiveTime::from_hms_opt(start as u32 / 60, start as u32 % 60, 0)?,
+    })
+}
+
+/// Circadian sheet with a row per labelled rhythm, e.g. the recording and its
+/// windows or every dog of a cohort.
+pub fn write_sheet(
+    workbook: &mut Workbook,
+    rows: &[(String, &Rhythm)],
+    translations: &Translations,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let bold_format = Format::new().set_bold().set_border(FormatBorder::Hair);
+    let decimal_format = Format::new().set_num_format(&config.decimals_format);
+    let ratio_format = Format::new().set_num_format("0.000");
+    let time_format = Format::new().set_num_format("hh:mm");
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.circadian"))?;
+    for (i, column) in COLUMNS.iter().enumerate() {
+        sheet.set_column_width(i as u16, 12)?;
+        sheet.write_with_format(0, i as u16, translations.get(&format!("column.{}", column)), &bold_format)?;
+    }
+    sheet.set_column_width(0, 20)?;
+
+    for (row, (label, rhythm)) in rows.iter().enumerate() {
+        let row = row as u32 + 1;
+        sheet.write(row, 0, label)?;
+        sheet.write(row, 1, rhythm.hours as u32)?;
+        for (column, value) in [(2, rhythm.is), (3, rhythm.iv), (4, rhythm.ra)] {
+            if let Some(value) = value {
+                sheet.write_with_format(row, column, value, &ratio_format)?;
+            }
+        }
+        for (column, level) in [(5, rhythm.l5), (7, rhythm.m10)] {
+            if let Some(level) = level {
+                let onset = ExcelDateTime::from_hms(level.onset.hour() as u16, level.onset.minute() as u8, 0)?;
+                sheet.write_with_format(row, column, level.counts, &decimal_format)?;
+                sheet.write_with_format(row, column + 1, &onset, &time_format)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeating_days_are_stable() {
+        let day: Vec<Option<f64>> = (0..24).map(|h| Some(if (8..20).contains(&h) { 100. } else { 10. })).collect();
+        let hours: Vec<Option<f64>> = day.iter().chain(day.iter()).chain(day.iter()).copied().collect();
+
+        assert!((interdaily_stability(&hours).unwrap() - 1.).abs() < 1e-12);
+        // two changes a day, six in 71 pairs of hours, over a variance of 45²
+        let iv = intradaily_variability(&hours).unwrap();
+        assert!((iv - (6. * 90f64.powi(2) / 71.) / 45f64.powi(2)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn missing_hours_break_the_series() {
+        let hours = [Some(1.), None, Some(3.), Some(5.)];
+
+        // only 3 -> 5 is a change, the variance is 8/3
+        assert!((intradaily_variability(&hours).unwrap() - 4. / (8. / 3.)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn levels_wrap_over_midnight() {
+        let profile: Vec<Option<f64>> = (0..MINUTES_PER_DAY).map(|m| Some(if (3 * 60..22 * 60).contains(&m) { 50. } else { 0. })).collect();
+
+        let l5 = level(&profile, L5_MINUTES, |a, b| a < b).unwrap();
+        assert_eq!(l5.counts, 0.);
+        assert_eq!(l5.onset, NaiveTime::from_hms_opt(22, 0, 0).unwrap());
+        let m10 = level(&profile, M10_MINUTES, |a, b| a > b).unwrap();
+        assert_eq!(m10.counts, 50.);
+        assert_eq!(m10.onset, NaiveTime::from_hms_opt(3, 0, 0).unwrap());
+    }
+}
@@ -0,0 +1,206 @@
+use std::error::Error;
+
+use rust_xlsxwriter::{Format, FormatBorder, Workbook, Worksheet};
+
+use crate::{
+    aggregate::{self, Summary},
+    analyse,
+    circadian::Rhythm,
+    comparison,
+    config::Config,
+    cosinor::{self, Fit},
+    i18n::Translations,
+    metadata::{self, DogMetadata},
+    metrics::{MetricRegistry, Unit},
+    stats::Description,
+    weeks,
+};
+
+/// One recording of the cohort with its metrics averaged over the analysed
+/// days. Time metrics are in minutes.
+#[derive(Debug, Clone)]
+pub struct CohortDog {
+    pub metadata: DogMetadata,
+    pub days: usize,
+    /// In the order of the metric registry, as are `weekdays` and `weekend`.
+    pub means: Vec<Option<f64>>,
+    pub weekdays: Vec<Option<f64>>,
+    pub weekend: Vec<Option<f64>>,
+    pub rhythm: Rhythm,
+    pub fits: Vec<Fit>,
+}
+
+/// Analyses every row of the metadata file. Each row names its recording in an
+/// `input_file` column and optionally its sheet in `input_file_sheet`, the rest
+/// of config.ini applies to all of them.
+pub fn collect(config: &Config, metrics: &MetricRegistry, translations: &Translations) -> Result<Vec<CohortDog>, String> {
+    let path = match &config.metadata_file {
+        Some(p) => p,
+        None => return Err("Can't find \"metadata_file\" attribute in the [general] section of config.ini. The cohort is read from it".to_string()),
+    };
+
+    let mut dogs = vec![];
+    for row in metadata::load_all(path)? {
+        let mut dog_config = config.clone();
+        dog_config.dog = row.dog.clone();
+        dog_config.input_file = match row.get("input_file") {
+            Some(f) => f.to_string(),
+            None => return Err(format!("Dog \"{}\" has no \"input_file\" in metadata file {}", row.dog, path)),
+        };
+        if let Some(sheet) = row.get("input_file_sheet") {
+            dog_config.input_file_sheet = sheet.to_string();
+        }
+
+        println!("Analysing {} from {}", row.dog, dog_config.input_file);
+        let analysis = analyse(&mut dog_config, Some(row.clone()), translations)?;
+        let summary = Summary::compute(&analysis, metrics, &dog_config);
+
+        let averages = |filter: &dyn Fn(&aggregate::DayValues) -> bool| -> Vec<Option<f64>> {
+            metrics
+                .metrics()
+                .iter()
+                .enumerate()
+                .map(|(index, metric)| {
+                    let mean = aggregate::mean(&summary.days, index, filter)?;
+                    match metric.unit() {
+                        Unit::Duration => Some(mean / 60.),
+                        _ => Some(mean),
+                    }
+                })
+                .collect()
+        };
+        dogs.push(CohortDog {
+            days: summary.days.len(),
+            means: averages(&|_| true),
+            weekdays: averages(&|d| !weeks::is_weekend(&d.date)),
+            weekend: averages(&|d| weeks::is_weekend(&d.date)),
+            rhythm: Rhythm::compute(&analysis, &dog_config),
+            fits: cosinor::fits(&analysis, &dog_config),
+            metadata: row,
+        });
+    }
+    Ok(dogs)
+}
+
+/// Cohort sheet with a row per dog and a Groups sheet describing every metric
+/// over all dogs and per value of every `[cohort] group_by` metadata field.
+/// With `paired_by` visits the groups only describe the baseline recordings,
+/// so every dog is counted once.
+pub fn write_workbook(
+    dogs: &[CohortDog],
+    metrics: &MetricRegistry,
+    translations: &Translations,
+    config: &Config,
+) -> Result<Workbook, Box<dyn Error>> {
+    for field in config.cohort_group_by.iter() {
+        if !dogs.iter().any(|d| d.metadata.get(field).is_some()) {
+            return Err(format!("Can't find metadata column \"{}\" from \"group_by\" in the [cohort] section of config.ini", field).into());
+        }
+    }
+
+    let bold_format = Format::new().set_bold().set_border(FormatBorder::Hair);
+    let decimal_format = Format::new().set_num_format(&config.decimals_format);
+    let mut workbook = Workbook::new();
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.cohort"))?;
+    let fields: Vec<&str> = dogs
+        .first()
+        .map(|d| d.metadata.fields.iter().map(|(k, _)| k.as_str()).collect())
+        .unwrap_or_default();
+
+    let mut headers = vec![translations.get("column.dog").to_string()];
+    headers.extend(fields.iter().map(|f| f.to_string()));
+    headers.push(translations.get("column.valid_days").to_string());
+    for suffix in [None, Some("label.weekdays"), Some("label.weekend")] {
+        for index in 0..metrics.metrics().len() {
+            let name = metric_header(metrics, translations, index);
+            headers.push(match suffix {
+                Some(key) => format!("{} ({})", name, translations.get(key)),
+                None => name,
+            });
+        }
+    }
+    write_headers(sheet, &headers, &bold_format)?;
+
+    for (row, dog) in dogs.iter().enumerate() {
+        let row = row as u32 + 1;
+        sheet.write(row, 0, &dog.metadata.dog)?;
+        for (i, field) in fields.iter().enumerate() {
+            sheet.write(row, 1 + i as u16, dog.metadata.get(field).unwrap_or_default())?;
+        }
+        let mut column = 1 + fields.len() as u16;
+        sheet.write(row, column, dog.days as u32)?;
+        for value in dog.means.iter().chain(dog.weekdays.iter()).chain(dog.weekend.iter()) {
+            column += 1;
+            if let Some(value) = value {
+                sheet.write_with_format(row, column, *value, &decimal_format)?;
+            }
+        }
+    }
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.groups"))?;
+    let headers: Vec<String> = ["field", "group", "metric", "n", "mean", "sd", "median", "q1", "q3", "iqr"]
+        .iter()
+        .map(|c| translations.get(&format!("column.{}", c)).to_string())
+        .collect();
+    write_headers(sheet, &headers, &bold_format)?;
+    sheet.set_column_width(2, 24)?;
+
+    let independent = comparison::independent(dogs, config);
+    let mut groups: Vec<(&str, String, Vec<&CohortDog>)> = vec![("", translations.get("label.all_dogs").to_string(), independent.clone())];
+    for field in config.cohort_group_by.iter() {
+        let mut values: Vec<&str> = independent.iter().filter_map(|d| d.metadata.get(field)).collect();
+        values.sort();
+        values.dedup();
+        for value in values {
+            let members = independent.iter().copied().filter(|d| d.metadata.get(field) == Some(value)).collect();
+            groups.push((field, value.to_string(), members));
+        }
+    }
+
+    let mut row = 0;
+    for (field, group, members) in groups.iter() {
+        let mut columns: Vec<(String, Vec<f64>)> = vec![(
+            translations.get("column.valid_days").to_string(),
+            members.iter().map(|d| d.days as f64).collect(),
+        )];
+        for index in 0..metrics.metrics().len() {
+            columns.push((metric_header(metrics, translations, index), members.iter().filter_map(|d| d.means[index]).collect()));
+        }
+        for (metric, values) in columns.iter() {
+            row += 1;
+            let description = Description::of(values);
+            sheet.write(row, 0, *field)?;
+            sheet.write(row, 1, group)?;
+            sheet.write(row, 2, metric)?;
+            sheet.write(row, 3, description.n as u32)?;
+            let statistics = [description.mean, description.sd, description.median, description.q1, description.q3, description.iqr()];
+            for (i, value) in statistics.iter().enumerate() {
+                if let Some(value) = value {
+                    sheet.write_with_format(row, 4 + i as u16, *value, &decimal_format)?;
+                }
+            }
+        }
+    }
+    Ok(workbook)
+}
+
+/// Metric name, with the unit for time metrics since they are in minutes here.
+pub fn metric_header(metrics: &MetricRegistry, translations: &Translations, index: usize) -> String {
+    let metric = &metrics.metrics()[index];
+    let name = translations.column(metric.id(), metric.name());
+    match metric.unit() {
+        Unit::Duration => format!("{} (min)", name),
+        _ => name.to_string(),
+    }
+}
+
+fn write_headers(sheet: &mut Worksheet, headers: &[String], bold_format: &Format) -> Result<(), Box<dyn Error>> {
+    for (i, header) in headers.iter().enumerate() {
+        sheet.set_column_width(i as u16, 14)?;
+        sheet.write_with_format(0, i as u16, header, bold_format)?;
+    }
+    Ok(())
+}
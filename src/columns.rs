@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use rust_xlsxwriter::row_col_to_cell;
+
+use crate::{config::Config, i18n::Translations, metrics::MetricRegistry};
+
+const DAILY_LEADING_COLUMNS: [(&str, &str); 3] = [("day", "Day"), ("date", "Date"), ("weekday", "Weekday")];
+const WEEKLY_LEADING_COLUMNS: [(&str, &str); 2] = [("label", "Label"), ("days", "Days")];
+
+/// Width of a column without a `width_<column>` override.
+const DEFAULT_WIDTH: f64 = 10.;
+
+/// Longest worksheet name Excel accepts.
+const MAX_SHEET_NAME: usize = 31;
+
+/// Column layout shared by the Daily and Weekly sheets. The Weekly sheet
+/// averages Daily cells, so its formulas are generated from the same layout
+/// the Daily sheet is written with. Columns are identified by metric id.
+#[derive(Debug, Clone)]
+pub struct ColumnRegistry {
+    daily_sheet: String,
+    weekly_sheet: String,
+    daily: Vec<String>,
+    weekly: Vec<String>,
+    headers: HashMap<String, String>,
+    widths: HashMap<String, f64>,
+}
+
+impl ColumnRegistry {
+    /// `daily` and `weekly` hold the metric ids of each sheet in order,
+    /// `headers` the header of every metric.
+    pub fn new(daily_sheet: &str, weekly_sheet: &str, daily: Vec<String>, weekly: Vec<String>, headers: HashMap<String, String>) -> Self {
+        let mut all_headers: HashMap<String, String> = DAILY_LEADING_COLUMNS
+            .iter()
+            .chain(WEEKLY_LEADING_COLUMNS.iter())
+            .map(|(id, header)| (id.to_string(), header.to_string()))
+            .collect();
+        all_headers.extend(headers);
+        Self {
+            daily_sheet: daily_sheet.to_string(),
+            weekly_sheet: weekly_sheet.to_string(),
+            daily,
+            weekly,
+            headers: all_headers,
+            widths: HashMap::new(),
+        }
+    }
+
+    /// Builds the layout from the `[output]` section. Both sheets default to
+    /// every enabled metric. Headers are translated and can be overridden per
+    /// column, like the widths.
+    pub fn from_config(metrics: &MetricRegistry, translations: &Translations, config: &Config) -> Result<Self, String> {
+        let enabled: Vec<String> = metrics.metrics().iter().map(|m| m.id().to_string()).collect();
+        let daily = config.output_daily.clone().unwrap_or_else(|| enabled.clone());
+        let weekly = config.output_weekly.clone().unwrap_or_else(|| daily.clone());
+
+        for (key, columns) in [("daily", &daily), ("weekly", &weekly)] {
+            for id in columns.iter() {
+                if !enabled.contains(id) {
+                    return Err(format!(
+                        "Unknown or disabled metric \"{}\" in the \"{}\" attribute of the [output] section of config.ini. Enabled metrics: {}",
+                        id,
+                        key,
+                        enabled.join(", "),
+                    ));
+                }
+            }
+        }
+        // the Weekly sheet averages the Daily cells
+        if let Some(id) = weekly.iter().find(|id| !daily.contains(id)) {
+            return Err(format!("Metric \"{}\" in the \"weekly\" attribute of the [output] section of config.ini must also be in \"daily\"", id));
+        }
+
+        let leading = DAILY_LEADING_COLUMNS
+            .iter()
+            .chain(WEEKLY_LEADING_COLUMNS.iter())
+            .map(|(id, header)| (id.to_string(), translations.column(id, header).to_string()));
+        let headers = metrics
+            .metrics()
+            .iter()
+            .map(|m| (m.id().to_string(), translations.column(m.id(), m.name()).to_string()))
+            .chain(leading)
+            .collect();
+        // every named window gets its own pair of sheets
+        for sheet in [translations.get("sheet.daily"), translations.get("sheet.weekly")] {
+            if let Some(window) = config.windows.iter().find(|w| window_sheet(sheet, &w.name).chars().count() > MAX_SHEET_NAME) {
+                return Err(format!(
+                    "Can't use \"{}\" attribute in the [windows] section of config.ini. Sheet name \"{}\" is longer than {} characters",
+                    window.name,
+                    window_sheet(sheet, &window.name),
+                    MAX_SHEET_NAME,
+                ));
+            }
+        }
+
+        let mut registry = Self::new(translations.get("sheet.daily"), translations.get("sheet.weekly"), daily, weekly, headers);
+        registry.headers.extend(config.column_labels.clone());
+        registry.widths = config.column_widths.clone();
+        Ok(registry)
+    }
+
+    /// Same layout written to other sheets, e.g. the ones of an analysis window.
+    pub fn with_sheets(&self, daily_sheet: &str, weekly_sheet: &str) -> Self {
+        let mut registry = self.clone();
+        registry.daily_sheet = daily_sheet.to_string();
+        registry.weekly_sheet = weekly_sheet.to_string();
+        registry
+    }
+
+    /// Same layout written to the Daily and Weekly sheets of a named window.
+    pub fn for_window(&self, name: &str) -> Self {
+        self.with_sheets(&window_sheet(&self.daily_sheet, name), &window_sheet(&self.weekly_sheet, name))
+    }
+
+    pub fn daily_sheet(&self) -> &str {
+        &self.daily_sheet
+    }
+
+    pub fn weekly_sheet(&self) -> &str {
+        &self.weekly_sheet
+    }
+
+    pub fn daily_columns(&self) -> Vec<&str> {
+        DAILY_LEADING_COLUMNS.iter().map(|(id, _)| *id).chain(self.daily.iter().map(|id| id.as_str())).collect()
+    }
+
+    pub fn weekly_columns(&self) -> Vec<&str> {
+        WEEKLY_LEADING_COLUMNS.iter().map(|(id, _)| *id).chain(self.weekly.iter().map(|id| id.as_str())).collect()
+    }
+
+    pub fn header<'a>(&'a self, column: &'a str) -> &'a str {
+        self.headers.get(column).map(|h| h.as_str()).unwrap_or(column)
+    }
+
+    pub fn width(&self, column: &str) -> f64 {
+        self.widths.get(column).copied().unwrap_or(DEFAULT_WIDTH)
+    }
+
+    pub fn daily_position(&self, column: &str) -> Option<u16> {
+        self.daily_columns().iter().position(|c| *c == column).map(|p| p as u16)
+    }
+
+    pub fn weekly_position(&self, column: &str) -> Option<u16> {
+        self.weekly_columns().iter().position(|c| *c == column).map(|p| p as u16)
+    }
+
+    /// Reference to a Daily cell, `row` being the zero-based row the Daily
+    /// sheet was written to.
+    pub fn daily_cell(&self, column: &str, row: u32) -> Option<String> {
+        let position = self.daily_position(column)?;
+        Some(format!("{}!{}", quote_sheet_name(&self.daily_sheet), row_col_to_cell(row, position)))
+    }
+
+    /// Weekly formula averaging `column` over the given zero-based Daily rows.
+    pub fn average_formula(&self, column: &str, daily_rows: &[u32]) -> Option<String> {
+        let cells = daily_rows
+            .iter()
+            .map(|row| self.daily_cell(column, *row))
+            .collect::<Option<Vec<_>>>()?;
+        Some(format!("=AVERAGE({})", cells.join(",")))
+    }
+}
+
+/// Name of a named window's copy of `sheet`, e.g. `Daily baseline`.
+fn window_sheet(sheet: &str, window: &str) -> String {
+    format!("{} {}", sheet, window)
+}
+
+pub fn quote_sheet_name(name: &str) -> String {
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        name.to_string()
+    } else {
+        format!("'{}'", name.replace('\'', "''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{intensity::Band, metrics::builtin_metrics, testing};
+    use rust_xlsxwriter::column_name_to_number;
+
+    fn builtin_registry(daily_sheet: &str) -> ColumnRegistry {
+        let metrics = builtin_metrics(&Band::defaults(50, 500, 1000));
+        let ids: Vec<String> = metrics.iter().map(|m| m.id().to_string()).collect();
+        let headers = metrics
+            .iter()
+            .map(|m| (m.id().to_string(), m.name().to_string()))
+            .collect();
+        ColumnRegistry::new(daily_sheet, "Weekly", ids.clone(), ids, headers)
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    /// Splits `=AVERAGE(Daily!D2,Daily!D3)` into `[("D", 2), ("D", 3)]`.
+    fn referenced_cells(formula: &str) -> Vec<(String, u32)> {
+        formula
+            .trim_start_matches("=AVERAGE(")
+            .trim_end_matches(')')
+            .split(',')
+            .map(|cell| {
+                let cell = cell.split('!').nth(1).unwrap();
+                let split = cell.find(|c: char| c.is_ascii_digit()).unwrap();
+                (cell[..split].to_string(), cell[split..].parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weekly_columns_average_the_daily_column_with_the_same_name() {
+        let registry = builtin_registry("Daily");
+        let daily_columns = registry.daily_columns();
+
+        for column in registry.weekly_columns().iter().skip(WEEKLY_LEADING_COLUMNS.len()) {
+            let formula = registry.average_formula(column, &[1, 2, 3]).unwrap();
+            for (letter, _) in referenced_cells(&formula) {
+                let position = column_name_to_number(&letter) as usize;
+                assert_eq!(daily_columns[position], *column, "{} averages column {}", column, letter);
+            }
+        }
+    }
+
+    #[test]
+    fn weekly_formulas_reference_the_rows_the_daily_sheet_wrote() {
+        let registry = builtin_registry("Daily");
+
+        // The first day is written to zero-based row 1, right below the header,
+        // which is row 2 in the formula.
+        let formula = registry.average_formula("total_vig", &[1, 2, 7]).unwrap();
+        assert_eq!(formula, "=AVERAGE(Daily!D2,Daily!D3,Daily!D8)");
+        assert_eq!(
+            referenced_cells(&formula).into_iter().map(|(_, row)| row).collect::<Vec<_>>(),
+            vec![2, 3, 8],
+        );
+    }
+
+    #[test]
+    fn added_columns_shift_the_references() {
+        let columns = ids(&["tot_counts", "total_vig"]);
+        let registry = ColumnRegistry::new("Daily", "Weekly", columns.clone(), columns, HashMap::new());
+
+        assert_eq!(registry.daily_cell("tot_counts", 1).unwrap(), "Daily!D2");
+        assert_eq!(registry.daily_cell("total_vig", 1).unwrap(), "Daily!E2");
+        assert!(registry.daily_cell("t_zero", 1).is_none());
+    }
+
+    #[test]
+    fn weekly_order_can_differ_from_daily() {
+        let registry = ColumnRegistry::new(
+            "Daily",
+            "Weekly",
+            ids(&["total_vig", "t_zero", "tot_counts"]),
+            ids(&["tot_counts", "total_vig"]),
+            HashMap::new(),
+        );
+
+        assert_eq!(registry.weekly_position("tot_counts"), Some(2));
+        assert_eq!(registry.average_formula("tot_counts", &[1]).unwrap(), "=AVERAGE(Daily!F2)");
+        assert_eq!(registry.average_formula("total_vig", &[1]).unwrap(), "=AVERAGE(Daily!D2)");
+    }
+
+    #[test]
+    fn sheet_names_with_spaces_are_quoted() {
+        let registry = builtin_registry("Dnevni podatki");
+
+        assert_eq!(registry.daily_cell("total_vig", 1).unwrap(), "'Dnevni podatki'!D2");
+    }
+
+    #[test]
+    fn window_sheets_keep_the_layout() {
+        let registry = builtin_registry("Daily").with_sheets("Daily baseline", "Weekly baseline");
+
+        assert_eq!(registry.weekly_sheet(), "Weekly baseline");
+        assert_eq!(registry.average_formula("total_vig", &[1]).unwrap(), "=AVERAGE('Daily baseline'!D2)");
+    }
+
+    #[test]
+    fn window_sheet_names_must_fit_excel() {
+        let from_config = |windows: &str| {
+            let config = testing::config(&format!("[format]\nlanguage=en\n[windows]\n{}", windows));
+            let metrics = MetricRegistry::from_config(&config).unwrap();
+            ColumnRegistry::from_config(&metrics, &Translations::from_config(&config).unwrap(), &config)
+        };
+
+        assert_eq!(from_config("baseline=1-7").unwrap().for_window("baseline").weekly_sheet(), "Weekly baseline");
+        let error = from_config("a_very_long_window_name_here=1-7").unwrap_err();
+        assert!(error.contains("a_very_long_window_name_here"), "{}", error);
+    }
+}
@@ -0,0 +1,221 @@
+use std::error::Error;
+
+use rust_xlsxwriter::{Format, FormatBorder, Workbook, Worksheet};
+
+use crate::{
+    cohort::{metric_header, CohortDog},
+    config::Config,
+    i18n::Translations,
+    metrics::MetricRegistry,
+    stats::{self, Description},
+};
+
+const GROUP_COLUMNS: [&str; 19] = [
+    "field", "group_a", "group_b", "metric", "n_a", "mean_a", "sd_a", "n_b", "mean_b", "sd_b", "difference", "t", "df", "p_t",
+    "hedges_g", "u", "z", "p_u", "r",
+];
+const PAIRED_COLUMNS: [&str; 13] = [
+    "field", "baseline", "followup", "metric", "pairs", "mean_baseline", "mean_followup", "mean_difference", "sd_difference",
+    "t", "df", "p_t", "dz",
+];
+
+/// Statistics sheet comparing every pair of groups of the `[cohort] compare_by`
+/// fields, and baseline with follow-up recordings of the same dogs when
+/// `paired_by` is set. With pairs, groups are compared on baseline recordings
+/// only so no dog is counted twice.
+pub fn write_statistics_sheet(
+    workbook: &mut Workbook,
+    dogs: &[CohortDog],
+    metrics: &MetricRegistry,
+    translations: &Translations,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    if config.compare_by.is_empty() && config.paired_by.is_none() {
+        return Ok(());
+    }
+    for field in config.compare_by.iter().chain(config.paired_by.iter()) {
+        if !dogs.iter().any(|d| d.metadata.get(field).is_some()) {
+            return Err(format!("Can't find metadata column \"{}\" from the [cohort] section of config.ini", field).into());
+        }
+    }
+
+    let formats = Formats {
+        bold: Format::new().set_bold().set_border(FormatBorder::Hair),
+        decimal: Format::new().set_num_format(&config.decimals_format),
+        p: Format::new().set_num_format("0.0000"),
+    };
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.statistics"))?;
+    for column in 0..GROUP_COLUMNS.len() as u16 {
+        sheet.set_column_width(column, 12)?;
+    }
+    sheet.set_column_width(3, 24)?;
+
+    let mut row = 0;
+    if !config.compare_by.is_empty() {
+        let independent = independent(dogs, config);
+        sheet.write_with_format(row, 0, translations.get("label.group_comparison"), &formats.bold)?;
+        row += 1;
+        write_headers(sheet, row, &GROUP_COLUMNS, translations, &formats.bold)?;
+        for field in config.compare_by.iter() {
+            let mut groups: Vec<&str> = independent.iter().filter_map(|d| d.metadata.get(field)).collect();
+            groups.sort();
+            groups.dedup();
+            for (i, first) in groups.iter().enumerate() {
+                for second in groups.iter().skip(i + 1) {
+                    for index in 0..metrics.metrics().len() {
+                        let values = |group: &str| -> Vec<f64> {
+                            independent
+                                .iter()
+                                .filter(|d| d.metadata.get(field) == Some(group))
+                                .filter_map(|d| d.means[index])
+                                .collect()
+                        };
+                        let (a, b) = (values(first), values(second));
+                        row += 1;
+                        sheet.write(row, 0, field)?;
+                        sheet.write(row, 1, *first)?;
+                        sheet.write(row, 2, *second)?;
+                        sheet.write(row, 3, metric_header(metrics, translations, index))?;
+                        write_group_row(sheet, row, &a, &b, &formats)?;
+                    }
+                }
+            }
+        }
+        row += 2;
+    }
+
+    if let Some(field) = &config.paired_by {
+        sheet.write_with_format(row, 0, translations.get("label.paired_comparison"), &formats.bold)?;
+        row += 1;
+        write_headers(sheet, row, &PAIRED_COLUMNS, translations, &formats.bold)?;
+        // the first follow-up recording of every dog with a baseline
+        let pairs: Vec<(&CohortDog, &CohortDog)> = dogs
+            .iter()
+            .filter(|d| is_visit(d, field, &config.paired_baseline))
+            .filter_map(|baseline| {
+                let followup = dogs.iter().find(|d| {
+                    d.metadata.dog.eq_ignore_ascii_case(&baseline.metadata.dog) && is_visit(d, field, &config.paired_followup)
+                })?;
+                Some((baseline, followup))
+            })
+            .collect();
+        for index in 0..metrics.metrics().len() {
+            let (before, after): (Vec<f64>, Vec<f64>) = pairs
+                .iter()
+                .filter_map(|(b, f)| Some((b.means[index]?, f.means[index]?)))
+                .unzip();
+            row += 1;
+            sheet.write(row, 0, field)?;
+            sheet.write(row, 1, &config.paired_baseline)?;
+            sheet.write(row, 2, &config.paired_followup)?;
+            sheet.write(row, 3, metric_header(metrics, translations, index))?;
+            write_paired_row(sheet, row, &before, &after, &formats)?;
+        }
+    }
+    Ok(())
+}
+
+struct Formats {
+    bold: Format,
+    decimal: Format,
+    p: Format,
+}
+
+/// The recordings that can be compared as independent samples: only the
+/// baseline ones when the cohort has `paired_by` visits of the same dogs.
+pub fn independent<'a>(dogs: &'a [CohortDog], config: &Config) -> Vec<&'a CohortDog> {
+    match &config.paired_by {
+        Some(field) => dogs.iter().filter(|d| is_visit(d, field, &config.paired_baseline)).collect(),
+        None => dogs.iter().collect(),
+    }
+}
+
+fn is_visit(dog: &CohortDog, field: &str, visit: &str) -> bool {
+    dog.metadata.get(field).is_some_and(|v| v.eq_ignore_ascii_case(visit))
+}
+
+fn write_group_row(sheet: &mut Worksheet, row: u32, a: &[f64], b: &[f64], formats: &Formats) -> Result<(), Box<dyn Error>> {
+    let (first, second) = (Description::of(a), Description::of(b));
+    let welch = stats::welch(a, b);
+    let mann_whitney = stats::mann_whitney(a, b);
+    sheet.write(row, 4, first.n as u32)?;
+    sheet.write(row, 7, second.n as u32)?;
+    let cells = [
+        (5, first.mean, &formats.decimal),
+        (6, first.sd, &formats.decimal),
+        (8, second.mean, &formats.decimal),
+        (9, second.sd, &formats.decimal),
+        (10, first.mean.zip(second.mean).map(|(a, b)| a - b), &formats.decimal),
+        (11, welch.map(|w| w.t), &formats.decimal),
+        (12, welch.map(|w| w.df), &formats.decimal),
+        (13, welch.map(|w| w.p), &formats.p),
+        (14, stats::hedges_g(a, b), &formats.decimal),
+        (15, mann_whitney.map(|m| m.u), &formats.decimal),
+        (16, mann_whitney.map(|m| m.z), &formats.decimal),
+        (17, mann_whitney.map(|m| m.p), &formats.p),
+        (18, mann_whitney.map(|m| m.r), &formats.decimal),
+    ];
+    write_cells(sheet, row, &cells)
+}
+
+fn write_paired_row(sheet: &mut Worksheet, row: u32, before: &[f64], after: &[f64], formats: &Formats) -> Result<(), Box<dyn Error>> {
+    let differences: Vec<f64> = after.iter().zip(before.iter()).map(|(a, b)| a - b).collect();
+    let test = stats::paired(before, after);
+    sheet.write(row, 4, differences.len() as u32)?;
+    let cells = [
+        (5, stats::mean(before), &formats.decimal),
+        (6, stats::mean(after), &formats.decimal),
+        (7, stats::mean(&differences), &formats.decimal),
+        (8, stats::sd(&differences), &formats.decimal),
+        (9, test.map(|t| t.t), &formats.decimal),
+        (10, test.map(|t| t.df), &formats.decimal),
+        (11, test.map(|t| t.p), &formats.p),
+        (12, stats::cohens_dz(before, after), &formats.decimal),
+    ];
+    write_cells(sheet, row, &cells)
+}
+
+/// Statistics that can't be computed, e.g. with too few dogs, stay empty.
+fn write_cells(sheet: &mut Worksheet, row: u32, cells: &[(u16, Option<f64>, &Format)]) -> Result<(), Box<dyn Error>> {
+    for (column, value, format) in cells.iter() {
+        if let Some(value) = value.filter(|v| v.is_finite()) {
+            sheet.write_with_format(row, *column, value, format)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_headers(sheet: &mut Worksheet, row: u32, columns: &[&str], translations: &Translations, bold_format: &Format) -> Result<(), Box<dyn Error>> {
+    for (i, column) in columns.iter().enumerate() {
+        sheet.write_with_format(row, i as u16, translations.get(&format!("column.{}", column)), bold_format)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circadian::Rhythm, metadata::DogMetadata, testing};
+
+    fn dog(name: &str, visit: &str) -> CohortDog {
+        CohortDog {
+            metadata: DogMetadata { dog: name.to_string(), fields: vec![("visit".to_string(), visit.to_string())] },
+            days: 7,
+            means: vec![],
+            weekdays: vec![],
+            weekend: vec![],
+            rhythm: Rhythm::default(),
+            fits: vec![],
+        }
+    }
+
+    #[test]
+    fn paired_dogs_are_independent_once() {
+        let dogs = [dog("billy", "baseline"), dog("billy", "followup"), dog("rex", "Baseline"), dog("luna", "followup")];
+
+        let names = |config: &Config| independent(&dogs, config).iter().map(|d| d.metadata.dog.as_str()).collect::<Vec<_>>();
+        assert_eq!(names(&testing::config("[cohort]\npaired_by=visit")), ["billy", "rex"]);
+        assert_eq!(names(&testing::config("")).len(), 4);
+    }
+}
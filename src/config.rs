@@ -0,0 +1,603 @@
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use chrono::{NaiveDate, NaiveDateTime, Weekday};
+use configparser::ini::Ini;
+
+use crate::{exclusions::Exclusion, intensity::Band, metrics::builtin_metrics};
+
+type Section = HashMap<String, Option<String>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EpochCheck {
+    Warn,
+    Fail,
+    Infer,
+}
+
+/// Part of a recording, bounded like the [parsing] window: by `start_datetime`
+/// and `end_datetime` where given, by `skip_days_num` and `day_window_size`
+/// otherwise. Named `[windows]` count their days from the first analysed day.
+#[derive(Debug, Clone)]
+pub struct WindowSpec {
+    pub name: String,
+    pub skip_days_num: i32,
+    pub day_window_size: i32,
+    pub start_datetime: Option<NaiveDateTime>,
+    pub end_datetime: Option<NaiveDateTime>,
+}
+
+impl WindowSpec {
+    /// Recording days like `1-7` or `8`, or a start and end date like
+    /// `2023-05-03 14:30, 2023-05-10 14:30`.
+    pub fn parse(name: &str, value: &str) -> Option<Self> {
+        if let Some((start, end)) = value.split_once(',') {
+            let (start, end) = (parse_datetime(start)?, parse_datetime(end)?);
+            return (start < end).then(|| Self {
+                name: name.to_string(),
+                skip_days_num: 0,
+                day_window_size: 0,
+                start_datetime: Some(start),
+                end_datetime: Some(end),
+            });
+        }
+        let (first, last) = value.split_once('-').unwrap_or((value, value));
+        let (first, last): (i32, i32) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+        (first >= 1 && first <= last).then(|| Self {
+            name: name.to_string(),
+            skip_days_num: first - 1,
+            day_window_size: last - first + 1,
+            start_datetime: None,
+            end_datetime: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    // [general]
+    pub input_file: String,
+    pub input_file_sheet: String,
+    pub output_file: String,
+    pub dog: String,
+    pub metadata_file: Option<String>,
+    pub exclusions_file: Option<String>,
+    pub diary_file: Option<String>,
+    /// Longest a diary event without an end lasts, in minutes.
+    pub diary_max_minutes: f64,
+
+    // [format]
+    pub decimals_format: String,
+    pub date_format: String,
+    pub time_format: String,
+    pub weekend_color: u32,
+    pub week_color: u32,
+    /// `en` or `sl`, `None` keeps the original English sheets with Slovenian
+    /// week labels.
+    pub language: Option<String>,
+    pub translation_file: Option<String>,
+
+    // [parsing]
+    pub skip_days_num: i32,
+    pub day_window_size: i32,
+    pub start_datetime: Option<NaiveDateTime>,
+    pub end_datetime: Option<NaiveDateTime>,
+    pub epoch_seconds: i32,
+    pub epoch_check: EpochCheck,
+    /// Intensity bands from the lowest, from `[bands]` or the `cutpoint_low`,
+    /// `cutpoint_moderate` and `cutpoint_vigorus` keys.
+    pub bands: Vec<Band>,
+    pub sort_epochs: bool,
+    pub drop_duplicates: bool,
+    pub fill_gaps: bool,
+    pub week_start: Weekday,
+
+    // [exclusions]
+    pub exclusions: Vec<Exclusion>,
+
+    // [metrics]
+    pub metrics_enabled: Option<Vec<String>>,
+    pub metrics_disabled: Vec<String>,
+
+    // [output]
+    pub output_daily: Option<Vec<String>>,
+    pub output_weekly: Option<Vec<String>>,
+    pub column_labels: HashMap<String, String>,
+    pub column_widths: HashMap<String, f64>,
+    pub weekly_formulas: bool,
+    pub tidy_csv: Option<String>,
+    pub tidy_parquet: Option<String>,
+    pub json_report: Option<String>,
+    pub epochs_sheet: bool,
+    pub epochs_csv: Option<String>,
+    pub html_report: Option<String>,
+    pub plots_dir: Option<String>,
+    /// `svg` or `png`.
+    pub plots_format: String,
+
+    // [bouts]
+    /// Index into `bands` of the lowest band counted as active.
+    pub bout_band: usize,
+    pub bout_min_minutes: f64,
+    pub bout_tolerance_epochs: usize,
+
+    // [targets], minutes per day by metric id
+    pub target_min: HashMap<String, f64>,
+    pub target_max: HashMap<String, f64>,
+
+    // [cohort]
+    pub cohort_output: Option<String>,
+    pub cohort_group_by: Vec<String>,
+    pub compare_by: Vec<String>,
+    /// Metadata column telling baseline and follow-up recordings apart.
+    pub paired_by: Option<String>,
+    pub paired_baseline: String,
+    pub paired_followup: String,
+
+    // [prepost]
+    pub prepost_output: Option<String>,
+    /// Second recording for the post window, the input file when missing.
+    pub post_input_file: Option<String>,
+    pub post_input_file_sheet: Option<String>,
+    pub pre_window: WindowSpec,
+    pub post_window: WindowSpec,
+
+    // [windows]
+    pub windows: Vec<WindowSpec>,
+
+    // [cosinor]
+    /// Periods in hours, the first one is also fitted alone.
+    pub cosinor_periods: Vec<f64>,
+
+    // [intensity]
+    /// Width in counts per minute of the bins of the intensity distribution.
+    pub intensity_bin_width: f64,
+    /// Number of bins, the last one is open ended.
+    pub intensity_bins: usize,
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self, String> {
+        match Ini::new().load(path) {
+            Ok(config) => Self::from_sections(&config),
+            Err(e) => Err(format!("Can't find {}! Make sure it's in the same folder.\nSource: {:#?}", path, e)),
+        }
+    }
+
+    /// Config from the text of a config.ini, for the tests.
+    #[cfg(test)]
+    pub fn read(text: &str) -> Result<Self, String> {
+        Self::from_sections(&Ini::new().read(text.to_string())?)
+    }
+
+    fn from_sections(config: &HashMap<String, Section>) -> Result<Self, String> {
+        let general = section(config, "general")?;
+        let format = section(config, "format")?;
+        let parsing = section(config, "parsing")?;
+        let no_section = Section::new();
+        let bouts = config.get("bouts").unwrap_or(&no_section);
+        let output = config.get("output").unwrap_or(&no_section);
+
+        let prepost = config.get("prepost").unwrap_or(&no_section);
+        let intensity = config.get("intensity").unwrap_or(&no_section);
+        let skip_days_num = required_int(parsing, "parsing", "skip_days_num")?;
+        let day_window_size = required_int(parsing, "parsing", "day_window_size")?;
+        let cutpoint_low = required_int(parsing, "parsing", "cutpoint_low")?;
+        let cutpoint_moderate = required_int(parsing, "parsing", "cutpoint_moderate")?;
+        let cutpoint_vigorus = required_int(parsing, "parsing", "cutpoint_vigorus")?;
+        let (bands, default_bout_band) = match config.get("bands").filter(|section| !section.is_empty()) {
+            // the lowest band above the resting one
+            Some(section) => (bands(section)?, 1),
+            None => (Band::defaults(cutpoint_low, cutpoint_moderate, cutpoint_vigorus), 2),
+        };
+        let bout_band = match optional_string(bouts, "band") {
+            None => default_bout_band,
+            Some(id) => match bands.iter().position(|b| b.id == id) {
+                Some(b) => b,
+                None => return Err(format!(
+                    "Can't find band \"{}\" of the \"band\" attribute in the [bouts] section of config.ini. Must be one of: {}",
+                    id,
+                    bands.iter().map(|b| b.id.as_str()).collect::<Vec<_>>().join(", "),
+                )),
+            },
+        };
+
+        let input_file = required_string(general, "general", "input_file")?;
+        let dog = match optional_string(general, "dog") {
+            Some(d) => d,
+            None => Path::new(&input_file)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+
+        Ok(Self {
+            input_file,
+            input_file_sheet: required_string(general, "general", "input_file_sheet")?,
+            output_file: required_string(general, "general", "output_file")?,
+            dog,
+            metadata_file: optional_string(general, "metadata_file"),
+            exclusions_file: optional_string(general, "exclusions_file"),
+            diary_file: optional_string(general, "diary_file"),
+            diary_max_minutes: match optional_number(general, "general", "diary_max_minutes", 240.)? {
+                minutes if minutes > 0. => minutes,
+                _ => return Err("Can't parse \"diary_max_minutes\" attribute in the [general] section of config.ini. Must be above 0".to_string()),
+            },
+
+            decimals_format: required_string(format, "format", "decimals")?,
+            date_format: required_string(format, "format", "date")?,
+            time_format: required_string(format, "format", "time")?,
+            weekend_color: required_color(format, "format", "weekend_color")?,
+            week_color: required_color(format, "format", "week_color")?,
+            language: optional_string(format, "language").map(|l| l.to_lowercase()),
+            translation_file: optional_string(format, "translation_file"),
+
+            skip_days_num,
+            day_window_size,
+            start_datetime: optional_datetime(parsing, "parsing", "start_datetime")?,
+            end_datetime: optional_datetime(parsing, "parsing", "end_datetime")?,
+            epoch_seconds: match required_int(parsing, "parsing", "epoch_seconds")? {
+                s if s > 0 => s,
+                _ => return Err("Can't parse \"epoch_seconds\" attribute in the [parsing] section of config.ini. Must be above 0".to_string()),
+            },
+            epoch_check: match optional_string(parsing, "epoch_check").map(|v| v.to_lowercase()).as_deref() {
+                None | Some("warn") => EpochCheck::Warn,
+                Some("fail") => EpochCheck::Fail,
+                Some("infer") => EpochCheck::Infer,
+                Some(_) => return Err("Can't parse \"epoch_check\" attribute in the [parsing] section of config.ini. Must be one of: warn, fail, infer".to_string()),
+            },
+            bands,
+            sort_epochs: optional_bool(parsing, "parsing", "sort_epochs", false)?,
+            drop_duplicates: optional_bool(parsing, "parsing", "drop_duplicates", false)?,
+            fill_gaps: optional_bool(parsing, "parsing", "fill_gaps", false)?,
+            week_start: match optional_string(parsing, "week_start").map(|v| v.to_lowercase()).as_deref() {
+                None | Some("iso") => Weekday::Mon,
+                Some(v) => match v.parse() {
+                    Ok(d) => d,
+                    Err(_) => return Err("Can't parse \"week_start\" attribute in the [parsing] section of config.ini. Must be iso or a weekday like mon".to_string()),
+                },
+            },
+
+            exclusions: exclusions(config.get("exclusions"))?,
+
+            metrics_enabled: config.get("metrics").and_then(|m| optional_list(m, "enabled")),
+            metrics_disabled: config.get("metrics").and_then(|m| optional_list(m, "disabled")).unwrap_or_default(),
+
+            output_daily: config.get("output").and_then(|o| optional_list(o, "daily")),
+            output_weekly: config.get("output").and_then(|o| optional_list(o, "weekly")),
+            column_labels: prefixed(config.get("output"), "label_")
+                .into_iter()
+                .map(|(column, value)| (column, value.to_string()))
+                .collect(),
+            column_widths: prefixed_numbers(config.get("output"), "output", "width_")?,
+            weekly_formulas: match config.get("output") {
+                Some(o) => optional_bool(o, "output", "weekly_formulas", false)?,
+                None => false,
+            },
+            tidy_csv: config.get("output").and_then(|o| optional_string(o, "tidy_csv")),
+            tidy_parquet: config.get("output").and_then(|o| optional_string(o, "tidy_parquet")),
+            json_report: config.get("output").and_then(|o| optional_string(o, "json")),
+            // the Epochs sheet used to come with the diary, keep it that way by default
+            epochs_sheet: optional_bool(output, "output", "epochs_sheet", optional_string(general, "diary_file").is_some())?,
+            epochs_csv: optional_string(output, "epochs_csv"),
+            html_report: optional_string(output, "html"),
+            plots_dir: optional_string(output, "plots_dir"),
+            plots_format: match optional_string(output, "plots_format").map(|f| f.to_lowercase()) {
+                None => "svg".to_string(),
+                Some(f) if f == "svg" || f == "png" => f,
+                Some(_) => return Err("Can't parse \"plots_format\" attribute in the [output] section of config.ini. Must be svg or png".to_string()),
+            },
+
+            bout_band,
+            bout_min_minutes: optional_number(bouts, "bouts", "min_minutes", 1.)?,
+            bout_tolerance_epochs: optional_number(bouts, "bouts", "tolerance_epochs", 0)?,
+
+            target_min: prefixed_numbers(config.get("targets"), "targets", "min_")?,
+            target_max: prefixed_numbers(config.get("targets"), "targets", "max_")?,
+
+            cohort_output: config.get("cohort").and_then(|c| optional_string(c, "output_file")),
+            cohort_group_by: config.get("cohort").and_then(|c| optional_list(c, "group_by")).unwrap_or_default(),
+            compare_by: config.get("cohort").and_then(|c| optional_list(c, "compare_by")).unwrap_or_default(),
+            paired_by: config.get("cohort").and_then(|c| optional_string(c, "paired_by")),
+            paired_baseline: config.get("cohort").and_then(|c| optional_string(c, "baseline")).unwrap_or("baseline".to_string()),
+            paired_followup: config.get("cohort").and_then(|c| optional_string(c, "followup")).unwrap_or("followup".to_string()),
+
+            prepost_output: optional_string(prepost, "output_file"),
+            post_input_file: optional_string(prepost, "post_input_file"),
+            post_input_file_sheet: optional_string(prepost, "post_input_file_sheet"),
+            pre_window: window_spec(prepost, "prepost", "pre_", "pre", skip_days_num, day_window_size)?,
+            post_window: window_spec(prepost, "prepost", "post_", "post", skip_days_num, day_window_size)?,
+
+            windows: windows(config.get("windows"))?,
+
+            cosinor_periods: cosinor_periods(config.get("cosinor"))?,
+
+            intensity_bin_width: match optional_number(intensity, "intensity", "bin_width", 100.)? {
+                w if w > 0. => w,
+                _ => return Err("Can't parse \"bin_width\" attribute in the [intensity] section of config.ini. Must be above 0".to_string()),
+            },
+            intensity_bins: match optional_number(intensity, "intensity", "bins", 50)? {
+                b if b >= 2 => b,
+                _ => return Err("Can't parse \"bins\" attribute in the [intensity] section of config.ini. Must be at least 2".to_string()),
+            },
+        })
+    }
+}
+
+impl Config {
+    /// Copy analysing only the given window of the recording.
+    pub fn with_window(&self, window: &WindowSpec) -> Self {
+        let mut config = self.clone();
+        config.skip_days_num = window.skip_days_num;
+        config.day_window_size = window.day_window_size;
+        config.start_datetime = window.start_datetime;
+        config.end_datetime = window.end_datetime;
+        config
+    }
+}
+
+fn section<'a>(config: &'a HashMap<String, Section>, name: &str) -> Result<&'a Section, String> {
+    match config.get(name) {
+        Some(s) => Ok(s),
+        None => Err(format!("Can't find [{}] section config.ini", name)),
+    }
+}
+
+fn optional_string(section: &Section, key: &str) -> Option<String> {
+    match section.get(key) {
+        Some(Some(v)) if !v.trim().is_empty() => Some(v.trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Comma separated list, e.g. `enabled=total_vig, total_mod`.
+fn optional_list(section: &Section, key: &str) -> Option<Vec<String>> {
+    optional_string(section, key).map(|v| {
+        v.split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    })
+}
+
+/// Values of every `<prefix><column>` key, keyed by column.
+fn prefixed<'a>(section: Option<&'a Section>, prefix: &str) -> Vec<(String, &'a str)> {
+    section
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| {
+            let column = key.strip_prefix(prefix)?;
+            Some((column.to_string(), value.as_deref()?.trim()))
+        })
+        .collect()
+}
+
+fn prefixed_numbers(section: Option<&Section>, section_name: &str, prefix: &str) -> Result<HashMap<String, f64>, String> {
+    let mut numbers = HashMap::new();
+    for (id, value) in prefixed(section, prefix) {
+        match value.parse() {
+            Ok(n) => numbers.insert(id, n),
+            Err(_) => return Err(format!("Can't parse \"{}{}\" attribute in the [{}] section of config.ini. Must be a number", prefix, id, section_name)),
+        };
+    }
+    Ok(numbers)
+}
+
+/// The window keys of [parsing] with a prefix, e.g. `pre_skip_days_num`. Missing
+/// day counts fall back to [parsing].
+fn window_spec(
+    section: &Section,
+    section_name: &str,
+    prefix: &str,
+    name: &str,
+    skip_days_num: i32,
+    day_window_size: i32,
+) -> Result<WindowSpec, String> {
+    Ok(WindowSpec {
+        name: name.to_string(),
+        skip_days_num: optional_number(section, section_name, &format!("{}skip_days_num", prefix), skip_days_num)?,
+        day_window_size: optional_number(section, section_name, &format!("{}day_window_size", prefix), day_window_size)?,
+        start_datetime: optional_datetime(section, section_name, &format!("{}start_datetime", prefix))?,
+        end_datetime: optional_datetime(section, section_name, &format!("{}end_datetime", prefix))?,
+    })
+}
+
+fn required_string(section: &Section, section_name: &str, key: &str) -> Result<String, String> {
+    match optional_string(section, key) {
+        Some(v) => Ok(v),
+        None => Err(format!("Can't find \"{}\" attribute in the [{}] section of config.ini", key, section_name)),
+    }
+}
+
+fn required_int(section: &Section, section_name: &str, key: &str) -> Result<i32, String> {
+    match required_string(section, section_name, key)?.parse() {
+        Ok(v) => Ok(v),
+        Err(_) => Err(format!("Can't parse \"{}\" attribute in the [{}] section of config.ini. Must be an integer", key, section_name)),
+    }
+}
+
+fn optional_number<T: FromStr>(section: &Section, section_name: &str, key: &str, default: T) -> Result<T, String> {
+    match optional_string(section, key) {
+        Some(v) => match v.parse() {
+            Ok(n) => Ok(n),
+            Err(_) => Err(format!("Can't parse \"{}\" attribute in the [{}] section of config.ini. Must be a number", key, section_name)),
+        },
+        None => Ok(default),
+    }
+}
+
+fn required_color(section: &Section, section_name: &str, key: &str) -> Result<u32, String> {
+    match u32::from_str_radix(&required_string(section, section_name, key)?, 16) {
+        Ok(v) => Ok(v),
+        Err(_) => Err(format!("Can't parse \"{}\" attribute in the [{}] section of config.ini. Must be an hex RGB.", key, section_name)),
+    }
+}
+
+fn optional_bool(section: &Section, section_name: &str, key: &str, default: bool) -> Result<bool, String> {
+    let value = match optional_string(section, key) {
+        Some(v) => v.to_lowercase(),
+        None => return Ok(default),
+    };
+    match value.as_str() {
+        "true" | "yes" | "y" | "1" => Ok(true),
+        "false" | "no" | "n" | "0" => Ok(false),
+        _ => Err(format!("Can't parse \"{}\" attribute in the [{}] section of config.ini. Must be true or false", key, section_name)),
+    }
+}
+
+/// Every key of the optional [exclusions] section names an interval, e.g. by
+/// its index, and its value is the `start, end, reason` of the interval, e.g.
+/// `1=2023-05-05 09:00, 2023-05-05 11:30, Vet visit`. Keys are lowercased, so
+/// the reason is kept in the value.
+fn exclusions(section: Option<&Section>) -> Result<Vec<Exclusion>, String> {
+    let section = match section {
+        Some(s) => s,
+        None => return Ok(vec![]),
+    };
+    let mut exclusions = vec![];
+    for (key, value) in section.iter() {
+        match value.as_deref().and_then(Exclusion::parse) {
+            Some(e) => exclusions.push(e),
+            None => return Err(format!("Can't parse \"{}\" attribute in the [exclusions] section of config.ini. Must be a start and end date and a reason like 2023-05-05 09:00, 2023-05-05 11:30, Vet visit", key)),
+        }
+    }
+    exclusions.sort_by_key(|e| e.start);
+    Ok(exclusions)
+}
+
+/// Every key of the optional [windows] section names a window, in recording order.
+fn windows(section: Option<&Section>) -> Result<Vec<WindowSpec>, String> {
+    let mut windows = vec![];
+    for (name, value) in section.into_iter().flatten() {
+        match value.as_deref().and_then(|v| WindowSpec::parse(name, v)) {
+            Some(w) => windows.push(w),
+            None => return Err(format!("Can't parse \"{}\" attribute in the [windows] section of config.ini. Must be recording days like 1-7 or a start and end date like 2023-05-03 14:30, 2023-05-10 14:30", name)),
+        }
+    }
+    // ordered by start once the first analysed day is known
+    windows.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(windows)
+}
+
+/// Every key of the [bands] section names a band, its value is the band's cutpoint.
+/// The names become `total_<name>` metric ids, so they can't take the id of
+/// another metric.
+fn bands(section: &Section) -> Result<Vec<Band>, String> {
+    let reserved: Vec<String> = builtin_metrics(&Band::defaults(0, 0, 0)).iter().map(|m| m.id().to_string()).collect();
+    let mut cutpoints: Vec<(String, i32)> = vec![];
+    for (id, value) in section.iter() {
+        if !id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+            return Err(format!("Can't use \"{}\" attribute in the [bands] section of config.ini. Band names may only have letters a-z, digits and _", id));
+        }
+        if reserved.contains(&format!("total_{}", id)) {
+            return Err(format!("Can't use \"{}\" attribute in the [bands] section of config.ini. Metric total_{} already exists", id, id));
+        }
+        match value.as_deref().and_then(|v| v.trim().parse().ok()) {
+            Some(cutpoint) if cutpoints.iter().any(|(_, c)| *c == cutpoint) => {
+                return Err(format!("Can't use \"{}\" attribute in the [bands] section of config.ini. Another band has the same cutpoint", id))
+            },
+            Some(cutpoint) if cutpoint >= 0 => cutpoints.push((id.clone(), cutpoint)),
+            _ => return Err(format!("Can't parse \"{}\" attribute in the [bands] section of config.ini. Must be a whole number of counts per epoch, 0 or above", id)),
+        }
+    }
+    if cutpoints.len() < 2 {
+        return Err("Can't find at least two bands in the [bands] section of config.ini".to_string());
+    }
+    Ok(Band::named(cutpoints))
+}
+
+fn cosinor_periods(section: Option<&Section>) -> Result<Vec<f64>, String> {
+    let periods = match section.and_then(|s| optional_list(s, "periods")) {
+        Some(p) => p,
+        None => return Ok(vec![24., 12.]),
+    };
+    match periods.iter().map(|p| p.parse::<f64>().ok().filter(|p| *p > 0.)).collect() {
+        Some(p) if !periods.is_empty() => Ok(p),
+        _ => Err("Can't parse \"periods\" attribute in the [cosinor] section of config.ini. Must be positive hours like 24, 12".to_string()),
+    }
+}
+
+fn optional_datetime(section: &Section, section_name: &str, key: &str) -> Result<Option<NaiveDateTime>, String> {
+    match optional_string(section, key) {
+        Some(v) => match parse_datetime(&v) {
+            Some(d) => Ok(Some(d)),
+            None => Err(format!("Can't parse \"{}\" attribute in the [{}] section of config.ini. Must be a date like 2023-05-03 14:30", key, section_name)),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Parses the date/time notations used in the config and the CSV side files.
+/// A date without a time means midnight.
+pub fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%d.%m.%Y %H:%M:%S", "%d.%m.%Y %H:%M"] {
+        if let Ok(d) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(d);
+        }
+    }
+    for format in ["%Y-%m-%d", "%d.%m.%Y"] {
+        if let Ok(d) = NaiveDate::parse_from_str(value, format) {
+            return d.and_hms_opt(0, 0, 0);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Datelike;
+
+    use crate::testing;
+
+    use super::*;
+
+    #[test]
+    fn epochs_must_have_a_length() {
+        assert_eq!(testing::config("[parsing]\nepoch_seconds=30").epoch_seconds, 30);
+        for seconds in ["0", "-15"] {
+            let text = format!("{}\n[parsing]\nepoch_seconds={}", testing::CONFIG, seconds);
+            assert!(Config::read(&text).unwrap_err().contains("epoch_seconds"));
+        }
+    }
+
+    #[test]
+    fn bands_default_to_the_cutpoints() {
+        let ids = |config: Config| config.bands.iter().map(|b| b.id.clone()).collect::<Vec<_>>();
+
+        assert_eq!(ids(testing::config("[bands]")), ["sedentary", "low", "moderate", "vigorus"]);
+        assert_eq!(ids(testing::config("[bands]\nrest=0\nactive=100\n[bouts]\nband=active")), ["rest", "active"]);
+        let text = format!("{}\n[bands]\nrest=0\nactive=100\n[bouts]\nband=moderate", testing::CONFIG);
+        assert!(Config::read(&text).unwrap_err().contains("rest, active"));
+    }
+
+    #[test]
+    fn bouts_start_at_moderate_or_the_lowest_band_above_rest() {
+        let bout_band = |config: Config| config.bands[config.bout_band].id.clone();
+
+        assert_eq!(bout_band(testing::config("")), "moderate");
+        assert_eq!(bout_band(testing::config("[bands]\nrest=0\nlight=50\nmoderate=500\nvigorous=1000")), "light");
+        assert_eq!(bout_band(testing::config("[bands]\nrest=0\nlight=50\nbrisk=500\n[bouts]\nband=brisk")), "brisk");
+    }
+
+    #[test]
+    fn band_names_and_cutpoints_are_checked() {
+        let error = |bands: &str| Config::read(&format!("{}\n[bouts]\nband=b\n[bands]\nb=100\n{}", testing::CONFIG, bands)).unwrap_err();
+
+        assert!(error("a=-5").contains("0 or above"));
+        assert!(error("a=100").contains("same cutpoint"));
+        assert!(error("a b=0").contains("a-z"));
+        assert!(error("a-b=0").contains("a-z"));
+        assert!(error("sed=0").contains("total_sed"));
+        assert!(error("a=x").contains("whole number"));
+    }
+
+    #[test]
+    fn exclusions_keep_their_reason() {
+        let config = testing::config(
+            "[exclusions]\n2=2023-05-06 09:00, 2023-05-06 10:00, Vet visit\n1=2023-05-05 09:00, 2023-05-05 10:00, Vet visit",
+        );
+
+        let reasons: Vec<(u32, &str)> = config.exclusions.iter().map(|e| (e.start.day(), e.reason.as_str())).collect();
+        assert_eq!(reasons, [(5, "Vet visit"), (6, "Vet visit")]);
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{config::{Config, EpochCheck}, SensorEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IssueKind {
+    Gap,
+    Duplicate,
+    OutOfOrder,
+    EpochMismatch,
+    Excluded,
+    OpenEvent,
+}
+
+impl IssueKind {
+    /// Identifier used in the exports.
+    pub fn id(&self) -> &'static str {
+        match self {
+            IssueKind::Gap => "gap",
+            IssueKind::Duplicate => "duplicate",
+            IssueKind::OutOfOrder => "out_of_order",
+            IssueKind::EpochMismatch => "epoch_length",
+            IssueKind::Excluded => "excluded",
+            IssueKind::OpenEvent => "open_event",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            IssueKind::Gap => "Gap",
+            IssueKind::Duplicate => "Duplicate",
+            IssueKind::OutOfOrder => "Out of order",
+            IssueKind::EpochMismatch => "Epoch length",
+            IssueKind::Excluded => "Excluded",
+            IssueKind::OpenEvent => "Open event",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QualityIssue {
+    pub kind: IssueKind,
+    pub timestamp: NaiveDateTime,
+    pub detail: String,
+    pub action: String,
+}
+
+/// Infers the epoch length as the most common difference between consecutive
+/// timestamps. Returns the length in seconds and the share of differences that
+/// agree with it.
+pub fn infer_epoch_seconds(entries: &[SensorEntry]) -> Option<(i32, f32)> {
+    let mut timestamps: Vec<_> = entries.iter().map(|e| e.timestamp()).collect();
+    timestamps.sort();
+
+    let mut differences: HashMap<i64, usize> = HashMap::new();
+    let mut total = 0;
+    for pair in timestamps.windows(2) {
+        let seconds = (pair[1] - pair[0]).num_seconds();
+        if seconds > 0 {
+            *differences.entry(seconds).or_default() += 1;
+            total += 1;
+        }
+    }
+
+    let (seconds, count) = differences.into_iter().max_by_key(|(seconds, count)| (*count, -seconds))?;
+    Some((seconds.try_into().ok()?, count as f32 / total as f32))
+}
+
+/// Cross-checks the configured `epoch_seconds` against the one inferred from
+/// the timestamps. Depending on `epoch_check` a mismatch is reported, fails the
+/// run or replaces the configured value.
+pub fn check_epoch(entries: &[SensorEntry], config: &mut Config) -> Result<Option<QualityIssue>, String> {
+    let (inferred, agreement) = match infer_epoch_seconds(entries) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    if inferred == config.epoch_seconds {
+        return Ok(None);
+    }
+
+    let detail = format!(
+        "Configured epoch_seconds={} but timestamps are {} seconds apart ({:.1}% of epochs)",
+        config.epoch_seconds,
+        inferred,
+        agreement * 100.,
+    );
+    let action = match config.epoch_check {
+        EpochCheck::Warn => "None".to_string(),
+        EpochCheck::Fail => return Err(detail),
+        EpochCheck::Infer => {
+            config.epoch_seconds = inferred;
+            format!("Using {} seconds", inferred)
+        },
+    };
+    Ok(Some(QualityIssue {
+        kind: IssueKind::EpochMismatch,
+        timestamp: entries.iter().map(|e| e.timestamp()).min().unwrap_or_default(),
+        detail,
+        action,
+    }))
+}
+
+/// Checks that the epochs follow each other exactly `epoch_seconds` apart.
+/// Depending on the config the entries get sorted, duplicated timestamps get
+/// dropped and missing epochs get filled with empty (-1) non-wear entries.
+/// Every problem found is returned so it can be written to the report.
+pub fn check(mut entries: Vec<SensorEntry>, config: &Config) -> (Vec<SensorEntry>, Vec<QualityIssue>) {
+    let mut issues = vec![];
+
+    // out-of-order rows
+    for pair in entries.windows(2) {
+        if pair[1].timestamp() < pair[0].timestamp() {
+            issues.push(QualityIssue {
+                kind: IssueKind::OutOfOrder,
+                timestamp: pair[1].timestamp(),
+                detail: format!("Follows {}", pair[0].timestamp()),
+                action: if config.sort_epochs { "Sorted".to_string() } else { "None".to_string() },
+            });
+        }
+    }
+    if config.sort_epochs {
+        entries.sort_by_key(|e| e.timestamp());
+    }
+
+    // duplicated timestamps
+    let mut seen = HashSet::new();
+    let mut kept = Vec::with_capacity(entries.len());
+    for entry in entries.into_iter() {
+        if seen.insert(entry.timestamp()) {
+            kept.push(entry);
+            continue;
+        }
+        issues.push(QualityIssue {
+            kind: IssueKind::Duplicate,
+            timestamp: entry.timestamp(),
+            detail: format!("Mag. Value {}", entry.value),
+            action: if config.drop_duplicates { "Dropped".to_string() } else { "None".to_string() },
+        });
+        if !config.drop_duplicates {
+            kept.push(entry);
+        }
+    }
+    let entries = kept;
+
+    // gaps between consecutive epochs
+    let epoch = Duration::seconds(config.epoch_seconds.into());
+    let mut out: Vec<SensorEntry> = Vec::with_capacity(entries.len());
+    let mut latest: Option<NaiveDateTime> = None;
+    for entry in entries.into_iter() {
+        if let Some(previous) = latest {
+            let difference = entry.timestamp() - previous;
+            if difference > epoch {
+                let missing = difference.num_seconds() / epoch.num_seconds() - 1;
+                let irregular = difference.num_seconds() % epoch.num_seconds() != 0;
+                issues.push(QualityIssue {
+                    kind: IssueKind::Gap,
+                    timestamp: previous + epoch,
+                    detail: format!(
+                        "{} missing epoch(s) until {}{}",
+                        missing,
+                        entry.timestamp(),
+                        if irregular { " (not aligned to epoch)" } else { "" },
+                    ),
+                    action: if config.fill_gaps { "Filled as non-wear".to_string() } else { "None".to_string() },
+                });
+                if config.fill_gaps {
+                    for i in 1..=missing {
+                        let timestamp = previous + epoch * i as i32;
+                        out.push(SensorEntry::empty(timestamp));
+                    }
+                }
+            }
+        }
+        if latest.is_none_or(|l| entry.timestamp() > l) {
+            latest = Some(entry.timestamp());
+        }
+        out.push(entry);
+    }
+
+    issues.sort_by_key(|i| i.timestamp);
+    (out, issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn timestamps(entries: &[SensorEntry]) -> Vec<String> {
+        entries.iter().map(|e| e.time.format("%H:%M:%S").to_string()).collect()
+    }
+
+    #[test]
+    fn gaps_are_reported_and_filled_on_request() {
+        let mut entries = testing::entries("2023-05-03 10:00", &[1, 2, 3, 4, 5]);
+        entries.drain(1..3);
+
+        let (kept, issues) = check(testing::entries("2023-05-03 10:00", &[1, 4]), &testing::config(""));
+        assert_eq!(kept.len(), 2);
+        assert!(issues.is_empty());
+
+        let (kept, issues) = check(entries, &testing::config("[parsing]\nfill_gaps=true"));
+        assert_eq!(timestamps(&kept), ["10:00:00", "10:00:15", "10:00:30", "10:00:45", "10:01:00"]);
+        assert_eq!(kept.iter().map(|e| e.value).collect::<Vec<_>>(), [1, -1, -1, 4, 5]);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, IssueKind::Gap);
+        assert!(issues[0].detail.starts_with("2 missing epoch(s)"));
+    }
+
+    #[test]
+    fn duplicates_and_out_of_order_epochs() {
+        let mut entries = testing::entries("2023-05-03 10:00", &[1, 2, 3]);
+        entries.swap(1, 2);
+        entries.push(testing::entries("2023-05-03 10:00:15", &[9]).remove(0));
+
+        let (kept, issues) = check(entries, &testing::config("[parsing]\nsort_epochs=true\ndrop_duplicates=true"));
+        assert_eq!(timestamps(&kept), ["10:00:00", "10:00:15", "10:00:30"]);
+        assert_eq!(kept.iter().map(|e| e.value).collect::<Vec<_>>(), [1, 2, 3]);
+        let kinds: Vec<IssueKind> = issues.iter().map(|i| i.kind).collect();
+        assert!(kinds.contains(&IssueKind::OutOfOrder));
+        assert!(kinds.contains(&IssueKind::Duplicate));
+        assert!(issues.iter().all(|i| i.action != "None"));
+    }
+
+    #[test]
+    fn epoch_length_is_the_most_common_step() {
+        let mut entries = testing::entries("2023-05-03 10:00", &[0; 10]);
+        entries.remove(4);
+
+        let (seconds, agreement) = infer_epoch_seconds(&entries).unwrap();
+        assert_eq!(seconds, 15);
+        assert!((agreement - 7. / 8.).abs() < 1e-6);
+
+        let mut config = testing::config("[parsing]\nepoch_seconds=30\nepoch_check=infer");
+        let issue = check_epoch(&entries, &mut config).unwrap().unwrap();
+        assert_eq!(issue.kind, IssueKind::EpochMismatch);
+        assert_eq!(config.epoch_seconds, 15);
+        let mut config = testing::config("[parsing]\nepoch_seconds=30\nepoch_check=fail");
+        assert!(check_epoch(&entries, &mut config).is_err());
+    }
+}
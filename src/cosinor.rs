@@ -0,0 +1,310 @@
+use std::{error::Error, f64::consts::PI};
+
+use rust_xlsxwriter::{Format, FormatBorder, Workbook, Worksheet};
+
+use crate::{circadian, config::Config, i18n::Translations, stats, Analysis};
+
+const CONFIDENCE: f64 = 0.95;
+const MINUTES_PER_DAY: usize = 24 * 60;
+
+/// A fitted value with its confidence interval, none when it has no closed form.
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+    pub value: f64,
+    pub ci: Option<(f64, f64)>,
+}
+
+/// Amplitude and acrophase of one period of the model. Acrophases are in hours
+/// after midnight and their bounds wrap around the period.
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub period: f64,
+    pub amplitude: Estimate,
+    pub acrophase: Estimate,
+}
+
+/// Least squares cosinor fit on the hourly means, in counts per minute.
+#[derive(Debug, Clone)]
+pub struct Fit {
+    pub periods: Vec<f64>,
+    pub hours: usize,
+    pub mesor: Estimate,
+    /// Half the range of the fitted day, from the component itself when there
+    /// is only one.
+    pub amplitude: Estimate,
+    /// Peak of the fitted day.
+    pub acrophase: Estimate,
+    pub r_squared: f64,
+    /// F-test of the model against a flat line.
+    pub p: f64,
+    pub components: Vec<Component>,
+}
+
+/// The single-component fit of the first `[cosinor] periods` entry and, with
+/// more of them, the multi-component fit of all.
+pub fn fits(analysis: &Analysis, config: &Config) -> Vec<Fit> {
+    let hours = circadian::hourly(analysis, config);
+    let mut models = vec![&config.cosinor_periods[..1]];
+    if config.cosinor_periods.len() > 1 {
+        models.push(&config.cosinor_periods);
+    }
+    models.iter().filter_map(|periods| fit(&hours, periods)).collect()
+}
+
+/// Fits `mesor + Σ β cos(2πt/period) + γ sin(2πt/period)` with `t` the middle
+/// of every hour. Amplitude and acrophase intervals come from the delta method.
+pub fn fit(hours: &[Option<f64>], periods: &[f64]) -> Option<Fit> {
+    let points: Vec<(f64, f64)> = hours
+        .iter()
+        .enumerate()
+        .filter_map(|(i, value)| Some((i as f64 + 0.5, (*value)?)))
+        .collect();
+    let parameters = 1 + 2 * periods.len();
+    if points.len() <= parameters {
+        return None;
+    }
+    let row = |t: f64| -> Vec<f64> {
+        let mut x = vec![1.];
+        for period in periods.iter() {
+            let angle = 2. * PI * t / period;
+            x.extend([angle.cos(), angle.sin()]);
+        }
+        x
+    };
+
+    let mut xtx = vec![vec![0.; parameters]; parameters];
+    let mut xty = vec![0.; parameters];
+    for (t, y) in points.iter() {
+        let x = row(*t);
+        for i in 0..parameters {
+            for j in 0..parameters {
+                xtx[i][j] += x[i] * x[j];
+            }
+            xty[i] += x[i] * y;
+        }
+    }
+    let inverse = invert(xtx)?;
+    let coefficients: Vec<f64> = inverse.iter().map(|r| r.iter().zip(xty.iter()).map(|(a, b)| a * b).sum()).collect();
+    let curve = |t: f64| -> f64 { row(t).iter().zip(coefficients.iter()).map(|(x, c)| x * c).sum() };
+
+    let values: Vec<f64> = points.iter().map(|(_, y)| *y).collect();
+    let mean = stats::mean(&values)?;
+    let total: f64 = values.iter().map(|y| (y - mean).powi(2)).sum();
+    let residual: f64 = points.iter().map(|(t, y)| (y - curve(*t)).powi(2)).sum();
+    if total == 0. {
+        return None;
+    }
+    let df = (points.len() - parameters) as f64;
+    let variance = residual / df;
+    let t = stats::t_critical(df, CONFIDENCE);
+    let estimate = |value: f64, variance: f64| Estimate {
+        value,
+        ci: Some((value - t * variance.sqrt(), value + t * variance.sqrt())),
+    };
+
+    let mut components = vec![];
+    for (k, period) in periods.iter().enumerate() {
+        let i = 1 + 2 * k;
+        let (beta, gamma) = (coefficients[i], coefficients[i + 1]);
+        let (var_beta, var_gamma, covariance) = (variance * inverse[i][i], variance * inverse[i + 1][i + 1], variance * inverse[i][i + 1]);
+        let amplitude = beta.hypot(gamma);
+        if amplitude == 0. {
+            continue;
+        }
+        let var_amplitude = (beta.powi(2) * var_beta + gamma.powi(2) * var_gamma + 2. * beta * gamma * covariance) / amplitude.powi(2);
+        let var_angle = (gamma.powi(2) * var_beta + beta.powi(2) * var_gamma - 2. * beta * gamma * covariance) / amplitude.powi(4);
+        let hours_per_radian = period / (2. * PI);
+        let acrophase = estimate(gamma.atan2(beta) * hours_per_radian, var_angle * hours_per_radian.powi(2));
+        components.push(Component {
+            period: *period,
+            amplitude: estimate(amplitude, var_amplitude),
+            acrophase: Estimate {
+                value: acrophase.value.rem_euclid(*period),
+                ci: acrophase.ci.map(|(low, high)| (low.rem_euclid(*period), high.rem_euclid(*period))),
+            },
+        });
+    }
+
+    let (amplitude, acrophase) = match &components[..] {
+        [single] if periods.len() == 1 => (single.amplitude, single.acrophase),
+        _ => {
+            let day: Vec<f64> = (0..MINUTES_PER_DAY).map(|m| curve(m as f64 / 60.)).collect();
+            let (peak, highest) = day.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+            let lowest = day.iter().copied().min_by(|a, b| a.total_cmp(b))?;
+            (
+                Estimate { value: (highest - lowest) / 2., ci: None },
+                Estimate { value: peak as f64 / 60., ci: None },
+            )
+        },
+    };
+    let f = ((total - residual) / (parameters - 1) as f64) / variance;
+    Some(Fit {
+        periods: periods.to_vec(),
+        hours: points.len(),
+        mesor: estimate(coefficients[0], variance * inverse[0][0]),
+        amplitude,
+        acrophase,
+        r_squared: 1. - residual / total,
+        p: stats::f_upper(f, (parameters - 1) as f64, df),
+        components,
+    })
+}
+
+/// Gauss-Jordan elimination with partial pivoting, none for a singular matrix.
+fn invert(mut matrix: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut inverse: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1. } else { 0. }).collect()).collect();
+    for column in 0..n {
+        let pivot = (column..n).max_by(|a, b| matrix[*a][column].abs().total_cmp(&matrix[*b][column].abs()))?;
+        if matrix[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        inverse.swap(column, pivot);
+        let scale = matrix[column][column];
+        for j in 0..n {
+            matrix[column][j] /= scale;
+            inverse[column][j] /= scale;
+        }
+        for row in 0..n {
+            if row == column {
+                continue;
+            }
+            let factor = matrix[row][column];
+            for j in 0..n {
+                matrix[row][j] -= factor * matrix[column][j];
+                inverse[row][j] -= factor * inverse[column][j];
+            }
+        }
+    }
+    Some(inverse)
+}
+
+/// Cosinor sheet with the fits of every labelled recording, window or dog, and
+/// below each multi-component fit a row per component.
+pub fn write_sheet(
+    workbook: &mut Workbook,
+    rows: &[(String, &[Fit])],
+    translations: &Translations,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let bold_format = Format::new().set_bold().set_border(FormatBorder::Hair);
+    let formats = [
+        Format::new().set_num_format(&config.decimals_format),
+        Format::new().set_num_format("hh:mm"),
+        Format::new().set_num_format("0.000"),
+        Format::new().set_num_format("0.0000"),
+    ];
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.cosinor"))?;
+
+    let mut headers: Vec<String> = ["label", "model", "hours"].iter().map(|c| translations.get(&format!("column.{}", c)).to_string()).collect();
+    for estimate in ["mesor", "amplitude", "acrophase"] {
+        let key = format!("column.{}", estimate);
+        let name = translations.get(&key);
+        headers.push(name.to_string());
+        headers.push(format!("{} {}", name, translations.get("column.ci_low")));
+        headers.push(format!("{} {}", name, translations.get("column.ci_high")));
+    }
+    headers.extend(["r_squared", "p"].iter().map(|c| translations.get(&format!("column.{}", c)).to_string()));
+    for (i, header) in headers.iter().enumerate() {
+        sheet.set_column_width(i as u16, 14)?;
+        sheet.write_with_format(0, i as u16, header, &bold_format)?;
+    }
+    sheet.set_column_width(0, 20)?;
+
+    let mut row = 0;
+    for (label, fits) in rows.iter() {
+        for fit in fits.iter() {
+            row += 1;
+            let model = fit.periods.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(" + ");
+            sheet.write(row, 0, label)?;
+            sheet.write(row, 1, format!("{} h", model))?;
+            sheet.write(row, 2, fit.hours as u32)?;
+            write_estimates(sheet, row, &[fit.mesor, fit.amplitude, fit.acrophase], &formats)?;
+            sheet.write_with_format(row, 12, fit.r_squared, &formats[2])?;
+            sheet.write_with_format(row, 13, fit.p, &formats[3])?;
+            if fit.periods.len() == 1 {
+                continue;
+            }
+            for component in fit.components.iter() {
+                row += 1;
+                sheet.write(row, 0, label)?;
+                sheet.write(row, 1, format!("{} h: {} h", model, component.period))?;
+                write_estimates(sheet, row, &[component.amplitude, component.acrophase], &formats)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the mesor, amplitude and acrophase columns from the last estimates
+/// given, so components without a mesor leave it empty.
+fn write_estimates(sheet: &mut Worksheet, row: u32, estimates: &[Estimate], formats: &[Format; 4]) -> Result<(), Box<dyn Error>> {
+    let first = 3 + 3 * (3 - estimates.len()) as u16;
+    for (i, estimate) in estimates.iter().enumerate() {
+        let column = first + 3 * i as u16;
+        // acrophases are clock times
+        let (scale, format) = match column {
+            9 => (1. / 24., &formats[1]),
+            _ => (1., &formats[0]),
+        };
+        sheet.write_with_format(row, column, estimate.value * scale, format)?;
+        if let Some((low, high)) = estimate.ci {
+            sheet.write_with_format(row, column + 1, low * scale, format)?;
+            sheet.write_with_format(row, column + 2, high * scale, format)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(curve: impl Fn(f64) -> f64) -> Vec<Option<f64>> {
+        (0..72).map(|i| Some(curve(i as f64 + 0.5))).collect()
+    }
+
+    #[test]
+    fn single_component_recovers_the_curve() {
+        let fit = fit(&hours(|t| 50. + 20. * (2. * PI * (t - 15.) / 24.).cos()), &[24.]).unwrap();
+
+        assert!((fit.mesor.value - 50.).abs() < 1e-9);
+        assert!((fit.amplitude.value - 20.).abs() < 1e-9);
+        assert!((fit.acrophase.value - 15.).abs() < 1e-9);
+        assert!((fit.r_squared - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn multi_component_splits_the_periods() {
+        let curve = |t: f64| 50. + 20. * (2. * PI * (t - 15.) / 24.).cos() + 5. * (2. * PI * (t - 3.) / 12.).cos();
+        let mut values = hours(curve);
+        values[10] = None;
+        let fit = fit(&values, &[24., 12.]).unwrap();
+
+        assert_eq!(fit.hours, 71);
+        assert!((fit.components[0].amplitude.value - 20.).abs() < 1e-9);
+        assert!((fit.components[1].amplitude.value - 5.).abs() < 1e-9);
+        assert!((fit.components[1].acrophase.value - 3.).abs() < 1e-9);
+        // both peak at 15:00
+        assert!((fit.acrophase.value - 15.).abs() < 1e-9);
+        // from 35 at 03:00 to 75
+        assert!((fit.amplitude.value - 20.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn noise_widens_the_intervals() {
+        let noise = [3., -2., 1., -4., 2., 0., -1., 4.];
+        let values: Vec<Option<f64>> = hours(|t| 50. + 20. * (2. * PI * (t - 15.) / 24.).cos())
+            .iter()
+            .enumerate()
+            .map(|(i, v)| v.map(|v| v + noise[i % noise.len()]))
+            .collect();
+        let fit = fit(&values, &[24.]).unwrap();
+
+        let (low, high) = fit.amplitude.ci.unwrap();
+        assert!(low < fit.amplitude.value && fit.amplitude.value < high);
+        assert!(fit.r_squared < 1. && fit.p < 1e-6);
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::{config::{parse_datetime, Config}, continuity::{IssueKind, QualityIssue}, intensity, SensorEntry};
+
+/// Label assigned to epochs that are not covered by any diary event.
+pub const NO_EVENT: &str = "No event";
+
+/// One owner diary entry. Without an `end` the event lasts until the next
+/// event in the diary starts, at most `diary_max_minutes`.
+#[derive(Debug, Clone)]
+pub struct DiaryEvent {
+    pub start: NaiveDateTime,
+    pub end: Option<NaiveDateTime>,
+    pub label: String,
+}
+
+#[derive(Debug, Default)]
+pub struct EventSummary {
+    pub label: String,
+    /// Events with the label that overlap the analysed days.
+    pub occurrences: usize,
+    pub epochs: i32,
+    pub total_counts: i64,
+    /// Epochs in every band of `[bands]`, from the lowest.
+    pub band_epochs: Vec<i32>,
+}
+
+/// Reads the diary CSV file with `start`, optional `end` and `label` columns.
+/// When the file has a `dog` column only rows of the given dog (or rows with
+/// the column left empty) are used.
+pub fn load_file(path: &str, dog: &str) -> Result<Vec<DiaryEvent>, String> {
+    let mut reader = match csv::ReaderBuilder::new().trim(csv::Trim::All).from_path(path) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Can't open diary file {}.\nSource: {}", path, e)),
+    };
+    let headers = match reader.headers() {
+        Ok(h) => h.clone(),
+        Err(e) => return Err(format!("Can't read the header row of diary file {}.\nSource: {}", path, e)),
+    };
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (start_column, label_column) = match (column("start"), column("label")) {
+        (Some(s), Some(l)) => (s, l),
+        _ => return Err(format!("Diary file {} needs \"start\" and \"label\" columns", path)),
+    };
+    let end_column = column("end");
+    let dog_column = column("dog");
+
+    let mut events = vec![];
+    for (line, record) in reader.records().enumerate() {
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Can't read diary file {}.\nSource: {}", path, e)),
+        };
+        if let Some(d) = dog_column.and_then(|c| record.get(c)) {
+            if !d.is_empty() && !d.eq_ignore_ascii_case(dog) {
+                continue;
+            }
+        }
+        let start = match record.get(start_column).and_then(parse_datetime) {
+            Some(s) => s,
+            None => return Err(format!("Can't parse the start of row {} in diary file {}", line + 2, path)),
+        };
+        let end = match end_column.and_then(|c| record.get(c)).filter(|v| !v.is_empty()) {
+            Some(v) => match parse_datetime(v) {
+                Some(e) => Some(e),
+                None => return Err(format!("Can't parse the end of row {} in diary file {}", line + 2, path)),
+            },
+            None => None,
+        };
+        let label = record.get(label_column).unwrap_or_default();
+        if label.is_empty() {
+            return Err(format!("Missing label in row {} of diary file {}", line + 2, path));
+        }
+        events.push(DiaryEvent { start, end, label: label.to_string() });
+    }
+    events.sort_by_key(|e| e.start);
+    Ok(events)
+}
+
+/// Sets the `event` of every epoch covered by a diary event. When events
+/// overlap the one that started last wins. Events without an end that were
+/// cut at `diary_max_minutes` are returned as quality issues.
+pub fn apply(sensor_data: &mut HashMap<NaiveDate, Vec<SensorEntry>>, events: &[DiaryEvent], config: &Config) -> Vec<QualityIssue> {
+    let (intervals, issues) = intervals(events, config);
+    for entries in sensor_data.values_mut() {
+        for entry in entries.iter_mut() {
+            let timestamp = entry.timestamp();
+            entry.event = intervals
+                .iter()
+                .rev()
+                .find(|(start, end, _)| timestamp >= *start && timestamp < *end)
+                .map(|(_, _, label)| label.to_string());
+        }
+    }
+    issues
+}
+
+/// Start, end and label of every event. Events without an end last until the
+/// next one starts, at most `diary_max_minutes`, the ones cut there are also
+/// returned as quality issues.
+fn intervals<'a>(events: &'a [DiaryEvent], config: &Config) -> (Vec<(NaiveDateTime, NaiveDateTime, &'a str)>, Vec<QualityIssue>) {
+    let max_duration = Duration::milliseconds((config.diary_max_minutes * 60_000.) as i64);
+    let mut issues = vec![];
+    let mut intervals = vec![];
+    for (i, event) in events.iter().enumerate() {
+        let end = match event.end {
+            Some(end) => end,
+            None => {
+                let capped = event.start + max_duration;
+                match events.get(i + 1).map(|next| next.start).filter(|next| *next <= capped) {
+                    Some(next) => next,
+                    None => {
+                        issues.push(QualityIssue {
+                            kind: IssueKind::OpenEvent,
+                            timestamp: event.start,
+                            detail: format!("{} has no end and no following event within {} minutes", event.label, config.diary_max_minutes),
+                            action: format!("Ended at {}", capped),
+                        });
+                        capped
+                    },
+                }
+            },
+        };
+        intervals.push((event.start, end, event.label.as_str()));
+    }
+    (intervals, issues)
+}
+
+/// Totals the worn epochs of every event type, in the order the labels first
+/// appear in the diary. Epochs without an event are collected last.
+pub fn summarize(
+    sensor_data: &HashMap<NaiveDate, Vec<SensorEntry>>,
+    events: &[DiaryEvent],
+    config: &Config,
+) -> Vec<EventSummary> {
+    let analysed = |start: NaiveDateTime, end: NaiveDateTime| {
+        sensor_data.keys().any(|date| {
+            let midnight = date.and_time(NaiveTime::MIN);
+            start < midnight + Duration::days(1) && end > midnight
+        })
+    };
+
+    let mut summaries: Vec<EventSummary> = vec![];
+    for (_, _, label) in intervals(events, config).0.into_iter().filter(|(start, end, _)| analysed(*start, *end)) {
+        match summaries.iter_mut().find(|s| s.label == label) {
+            Some(s) => s.occurrences += 1,
+            None => summaries.push(EventSummary {
+                label: label.to_string(),
+                occurrences: 1,
+                band_epochs: vec![0; config.bands.len()],
+                ..Default::default()
+            }),
+        }
+    }
+    summaries.push(EventSummary {
+        label: NO_EVENT.to_string(),
+        band_epochs: vec![0; config.bands.len()],
+        ..Default::default()
+    });
+
+    for entry in sensor_data.values().flatten().filter(|e| e.value >= 0) {
+        let label = entry.event.as_deref().unwrap_or(NO_EVENT);
+        let summary = match summaries.iter_mut().find(|s| s.label == label) {
+            Some(s) => s,
+            None => continue,
+        };
+        summary.epochs += 1;
+        summary.total_counts += entry.value as i64;
+        if let Some(band) = intensity::band_index(entry.value, &config.bands) {
+            summary.band_epochs[band] += 1;
+        }
+    }
+
+    summaries.retain(|s| s.epochs > 0 || s.label != NO_EVENT);
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn event(start: &str, end: Option<&str>, label: &str) -> DiaryEvent {
+        DiaryEvent {
+            start: parse_datetime(start).unwrap(),
+            end: end.and_then(parse_datetime),
+            label: label.to_string(),
+        }
+    }
+
+    fn events_of(sensor_data: &HashMap<NaiveDate, Vec<SensorEntry>>) -> Vec<Option<String>> {
+        let mut entries: Vec<&SensorEntry> = sensor_data.values().flatten().collect();
+        entries.sort_by_key(|e| e.timestamp());
+        entries.iter().map(|e| e.event.clone()).collect()
+    }
+
+    #[test]
+    fn events_cover_their_epochs() {
+        let mut sensor_data = testing::by_day(testing::entries("2023-05-03 10:00", &[0; 6]));
+        let events = [
+            event("2023-05-03 10:00:15", Some("2023-05-03 10:00:45"), "walk"),
+            event("2023-05-03 10:00:30", Some("2023-05-03 10:01:00"), "play"),
+        ];
+
+        apply(&mut sensor_data, &events, &testing::config(""));
+        let labels = events_of(&sensor_data);
+        let expected = [None, Some("walk"), Some("play"), Some("play"), None, None];
+        assert_eq!(labels, expected.map(|l| l.map(str::to_string)));
+    }
+
+    #[test]
+    fn events_are_totalled_by_label() {
+        let mut sensor_data = testing::by_day(testing::entries("2023-05-03 10:00", &[10, 600, 1200, -1, 60, 0]));
+        let events = [
+            event("2023-05-03 10:00:15", Some("2023-05-03 10:00:45"), "walk"),
+            event("2023-05-03 10:00:45", Some("2023-05-03 10:01:15"), "walk"),
+        ];
+        apply(&mut sensor_data, &events, &testing::config(""));
+
+        let summaries = summarize(&sensor_data, &events, &testing::config(""));
+        assert_eq!(summaries.len(), 2);
+        let walk = &summaries[0];
+        assert_eq!((walk.label.as_str(), walk.occurrences, walk.epochs, walk.total_counts), ("walk", 2, 3, 1860));
+        assert_eq!(walk.band_epochs, [0, 1, 1, 1]);
+        assert_eq!((summaries[1].label.as_str(), summaries[1].epochs, &summaries[1].band_epochs[..]), (NO_EVENT, 2, &[2, 0, 0, 0][..]));
+    }
+
+    #[test]
+    fn events_are_totalled_by_the_configured_bands() {
+        let mut sensor_data = testing::by_day(testing::entries("2023-05-03 10:00", &[10, 600, 1200, 2500]));
+        let events = [event("2023-05-03 10:00", Some("2023-05-03 10:01"), "walk")];
+        apply(&mut sensor_data, &events, &testing::config(""));
+
+        let config = testing::config("[bands]\nrest=0\nactive=500\nvery_active=2000\n[bouts]\nband=active");
+        let summaries = summarize(&sensor_data, &events, &config);
+        assert_eq!(summaries[0].band_epochs, [1, 2, 1]);
+    }
+
+    #[test]
+    fn open_events_end_at_the_next_event_or_the_maximum_duration() {
+        let mut sensor_data = testing::by_day(testing::entries("2023-05-03 10:00", &[0; 8]));
+        let events = [
+            event("2023-05-03 10:00:15", None, "walk"),
+            event("2023-05-03 10:00:45", None, "sleep"),
+        ];
+
+        let issues = apply(&mut sensor_data, &events, &testing::config("[general]\ndiary_max_minutes=0.5"));
+        let labels = events_of(&sensor_data);
+        let expected = [None, Some("walk"), Some("walk"), Some("sleep"), Some("sleep"), None, None, None];
+        assert_eq!(labels, expected.map(|l| l.map(str::to_string)));
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].kind, issues[0].timestamp), (IssueKind::OpenEvent, events[1].start));
+    }
+
+    #[test]
+    fn events_outside_the_analysed_days_are_not_counted() {
+        let mut sensor_data = testing::by_day(testing::entries("2023-05-03 10:00", &[10, 20]));
+        let events = [
+            event("2023-05-02 10:00", Some("2023-05-02 11:00"), "walk"),
+            event("2023-05-03 09:00", Some("2023-05-03 10:00:15"), "walk"),
+            event("2023-05-04 10:00", None, "walk"),
+        ];
+        apply(&mut sensor_data, &events, &testing::config(""));
+
+        let summaries = summarize(&sensor_data, &events, &testing::config(""));
+        assert_eq!((summaries[0].occurrences, summaries[0].epochs, summaries[0].total_counts), (1, 1, 10));
+    }
+}
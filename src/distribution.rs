@@ -0,0 +1,97 @@
+use std::error::Error;
+
+use rust_xlsxwriter::{Format, FormatBorder, Workbook};
+
+use crate::{calc_date, config::Config, i18n::Translations, sorted_dates, stats, Analysis, SensorEntry};
+
+/// Minutes spent in every bin of `[intensity] bin_width` counts per minute,
+/// non-wear epochs left out. The last bin holds everything above.
+pub fn minutes_per_bin(entries: &[SensorEntry], config: &Config) -> Vec<f64> {
+    let per_minute = 60. / config.epoch_seconds as f64;
+    let mut minutes = vec![0.; config.intensity_bins];
+    for entry in entries.iter().filter(|e| e.value >= 0) {
+        let bin = (entry.value as f64 * per_minute / config.intensity_bin_width) as usize;
+        minutes[bin.min(config.intensity_bins - 1)] += 1. / per_minute;
+    }
+    minutes
+}
+
+/// Slope and intercept of the log of the minutes over the log of the bin
+/// middles, bins without any time left out. Steeper negative slopes mean less
+/// time at higher intensities.
+pub fn intensity_gradient(minutes: &[f64], bin_width: f64) -> Option<(f64, f64)> {
+    let (x, y): (Vec<f64>, Vec<f64>) = minutes
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| **m > 0.)
+        .map(|(bin, m)| (((bin as f64 + 0.5) * bin_width).ln(), m.ln()))
+        .unzip();
+    let (mean_x, mean_y) = (stats::mean(&x)?, stats::mean(&y)?);
+    let spread: f64 = x.iter().map(|x| (x - mean_x).powi(2)).sum();
+    if spread == 0. {
+        return None;
+    }
+    let slope = x.iter().zip(y.iter()).map(|(x, y)| (x - mean_x) * (y - mean_y)).sum::<f64>() / spread;
+    Some((slope, mean_y - slope * mean_x))
+}
+
+/// MX metric: the counts per minute above which the most active `minutes` of
+/// the day were spent, none when fewer minutes were worn.
+pub fn mx(entries: &[SensorEntry], minutes: f64, config: &Config) -> Option<f64> {
+    let per_minute = 60. / config.epoch_seconds as f64;
+    let mut values: Vec<i32> = entries.iter().map(|e| e.value).filter(|v| *v >= 0).collect();
+    values.sort_by(|a, b| b.cmp(a));
+    let rank = (minutes * per_minute).ceil() as usize;
+    values.get(rank.checked_sub(1)?).map(|v| *v as f64 * per_minute)
+}
+
+/// Minutes per intensity bin of every analysed day.
+pub fn write_sheet(workbook: &mut Workbook, analysis: &Analysis, translations: &Translations, config: &Config) -> Result<(), Box<dyn Error>> {
+    let bold_format = Format::new().set_bold().set_border(FormatBorder::Hair);
+    let decimal_format = Format::new().set_num_format(&config.decimals_format);
+    let date_format = Format::new().set_num_format(&config.date_format);
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.distribution"))?;
+
+    let width = config.intensity_bin_width;
+    let mut headers = vec![translations.get("column.day").to_string(), translations.get("column.date").to_string()];
+    headers.extend((0..config.intensity_bins - 1).map(|bin| format!("{}–{}", bin as f64 * width, (bin + 1) as f64 * width)));
+    headers.push(format!("≥ {}", (config.intensity_bins - 1) as f64 * width));
+    for (i, header) in headers.iter().enumerate() {
+        sheet.write_with_format(0, i as u16, header, &bold_format)?;
+    }
+    sheet.set_column_width(1, 12)?;
+
+    for (index, date) in sorted_dates(&analysis.sensor_data).iter().enumerate() {
+        let row = index as u32 + 1;
+        sheet.write(row, 0, row)?;
+        sheet.write_with_format(row, 1, &calc_date(date)?, &date_format)?;
+        let minutes = minutes_per_bin(&analysis.sensor_data[date], config);
+        for (bin, value) in minutes.iter().enumerate() {
+            sheet.write_with_format(row, 2 + bin as u16, *value, &decimal_format)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_of_a_power_law() {
+        // 600 minutes around 25 counts/min, falling with the square of the intensity
+        let minutes: Vec<f64> = (0..20).map(|bin| 600. * ((bin as f64 + 0.5) / 0.5).powi(-2)).collect();
+        let (slope, intercept) = intensity_gradient(&minutes, 50.).unwrap();
+
+        assert!((slope + 2.).abs() < 1e-9);
+        assert!((intercept - (600f64.ln() + 2. * 25f64.ln())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn gradient_skips_empty_bins() {
+        assert_eq!(intensity_gradient(&[30., 0., 0.], 100.), None);
+        let (slope, _) = intensity_gradient(&[30., 0., 10.], 100.).unwrap();
+        assert!((slope - (10f64 / 30.).ln() / 5f64.ln()).abs() < 1e-9);
+    }
+}
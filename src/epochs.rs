@@ -0,0 +1,118 @@
+use chrono::NaiveDateTime;
+
+use crate::{config::Config, intensity::Intensity, Analysis};
+
+/// Rows of a worksheet, including the header row.
+pub const XLSX_MAX_ROWS: usize = 1_048_576;
+
+const CSV_COLUMNS: [&str; 7] = ["timestamp", "counts", "intensity", "non_wear", "bout", "exclusion", "event"];
+
+/// One epoch with everything it was classified as.
+#[derive(Debug, Clone)]
+pub struct EpochRow<'a> {
+    pub timestamp: NaiveDateTime,
+    /// -1 for non-wear epochs filled into gaps.
+    pub value: i32,
+    pub intensity: Intensity<'a>,
+    /// One-based index into the detected bouts.
+    pub bout: Option<usize>,
+    pub exclusion: Option<&'a str>,
+    pub event: Option<&'a str>,
+}
+
+/// Every analysed and excluded epoch in time order.
+pub fn annotate<'a>(analysis: &'a Analysis, config: &'a Config) -> Vec<EpochRow<'a>> {
+    let mut rows: Vec<EpochRow> = analysis
+        .sensor_data
+        .values()
+        .chain(analysis.excluded_data.values())
+        .flatten()
+        .map(|entry| EpochRow {
+            timestamp: entry.timestamp(),
+            value: entry.value,
+            intensity: Intensity::classify(entry.value, &config.bands),
+            bout: None,
+            exclusion: entry.exclusion.as_deref(),
+            event: entry.event.as_deref(),
+        })
+        .collect();
+    rows.sort_by_key(|r| r.timestamp);
+
+    // both are sorted, so one pass assigns every bout
+    let mut bouts = analysis.bouts.iter().enumerate().peekable();
+    for row in rows.iter_mut() {
+        while bouts.peek().is_some_and(|(_, b)| b.end <= row.timestamp) {
+            bouts.next();
+        }
+        if let Some((index, bout)) = bouts.peek() {
+            if row.exclusion.is_none() && bout.start <= row.timestamp {
+                row.bout = Some(index + 1);
+            }
+        }
+    }
+    rows
+}
+
+pub fn write_csv(path: &str, rows: &[EpochRow]) -> Result<(), String> {
+    match write(path, rows) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Can't write epochs file {}.\nSource: {}", path, e)),
+    }
+}
+
+fn write(path: &str, rows: &[EpochRow]) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(CSV_COLUMNS)?;
+    for row in rows.iter() {
+        writer.write_record([
+            row.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            row.value.to_string(),
+            row.intensity.id().to_string(),
+            (row.intensity == Intensity::NonWear).to_string(),
+            row.bout.map(|b| b.to_string()).unwrap_or_default(),
+            row.exclusion.unwrap_or_default().to_string(),
+            row.event.unwrap_or_default().to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bouts, exclusions::{self, Exclusion}, testing, window::AnalysisWindow};
+
+    #[test]
+    fn epochs_are_annotated_in_time_order() {
+        let config = testing::config("[bouts]\nmin_minutes=0.5");
+        let mut sensor_data = testing::by_day(testing::entries("2023-05-03 10:00", &[-1, 20, 600, 1200, 700, 80]));
+        let exclusions = vec![Exclusion::parse("2023-05-03 10:01:00, 2023-05-03 10:01:15, bath").unwrap()];
+        let (excluded_data, _) = exclusions::apply(&mut sensor_data, &exclusions);
+        let analysis = Analysis {
+            metadata: None,
+            window: AnalysisWindow::default(),
+            bouts: bouts::detect(&sensor_data, &config),
+            exclusions,
+            sensor_data,
+            excluded_data,
+            quality_issues: vec![],
+            event_summaries: None,
+        };
+
+        let rows = annotate(&analysis, &config);
+        let intensities: Vec<&str> = rows.iter().map(|r| r.intensity.id()).collect();
+        assert_eq!(intensities, ["non_wear", "sedentary", "moderate", "vigorus", "moderate", "low"]);
+        assert_eq!(rows.iter().map(|r| r.bout).collect::<Vec<_>>(), [None, None, Some(1), Some(1), None, None]);
+        assert_eq!(rows.iter().map(|r| r.exclusion).collect::<Vec<_>>(), [None, None, None, None, Some("bath"), None]);
+
+        let path = std::env::temp_dir().join("epochs_are_annotated_in_time_order.csv");
+        write_csv(path.to_str().unwrap(), &rows).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_COLUMNS.join(","));
+        assert_eq!(lines[1], "2023-05-03 10:00:00,-1,non_wear,true,,,");
+        assert_eq!(lines[3], "2023-05-03 10:00:30,600,moderate,false,1,,");
+        assert_eq!(lines.len(), 7);
+    }
+}
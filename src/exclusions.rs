@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{config::parse_datetime, continuity::{IssueKind, QualityIssue}, SensorEntry};
+
+/// A time interval to leave out of every metric, e.g. a vet visit or a bath.
+/// `start` is inclusive and `end` exclusive.
+#[derive(Debug, Clone)]
+pub struct Exclusion {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub reason: String,
+}
+
+impl Exclusion {
+    /// Parses a `start, end, reason` value as written in the [exclusions]
+    /// section. Without a reason the interval is just `Excluded`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.splitn(3, ',');
+        let start = parse_datetime(parts.next()?)?;
+        let end = parse_datetime(parts.next()?)?;
+        let reason = parts.next().map(str::trim).filter(|r| !r.is_empty()).unwrap_or("Excluded");
+        if end <= start {
+            return None;
+        }
+        Some(Self { start, end, reason: reason.to_string() })
+    }
+
+    fn contains(&self, timestamp: NaiveDateTime) -> bool {
+        timestamp >= self.start && timestamp < self.end
+    }
+}
+
+/// Reads the exclusions CSV file with `start`, `end` and `reason` columns.
+/// When the file has a `dog` column only rows of the given dog (or rows with
+/// the column left empty) are used.
+pub fn load_file(path: &str, dog: &str) -> Result<Vec<Exclusion>, String> {
+    let mut reader = match csv::ReaderBuilder::new().trim(csv::Trim::All).from_path(path) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Can't open exclusions file {}.\nSource: {}", path, e)),
+    };
+    let headers = match reader.headers() {
+        Ok(h) => h.clone(),
+        Err(e) => return Err(format!("Can't read the header row of exclusions file {}.\nSource: {}", path, e)),
+    };
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (start_column, end_column) = match (column("start"), column("end")) {
+        (Some(s), Some(e)) => (s, e),
+        _ => return Err(format!("Exclusions file {} needs \"start\" and \"end\" columns", path)),
+    };
+    let reason_column = column("reason");
+    let dog_column = column("dog");
+
+    let mut exclusions = vec![];
+    for (line, record) in reader.records().enumerate() {
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Can't read exclusions file {}.\nSource: {}", path, e)),
+        };
+        if let Some(d) = dog_column.and_then(|c| record.get(c)) {
+            if !d.is_empty() && !d.eq_ignore_ascii_case(dog) {
+                continue;
+            }
+        }
+        let start = record.get(start_column).and_then(parse_datetime);
+        let end = record.get(end_column).and_then(parse_datetime);
+        let (start, end) = match (start, end) {
+            (Some(s), Some(e)) if e > s => (s, e),
+            _ => return Err(format!("Can't parse the start/end of row {} in exclusions file {}", line + 2, path)),
+        };
+        let reason = reason_column.and_then(|c| record.get(c)).unwrap_or("Excluded");
+        exclusions.push(Exclusion { start, end, reason: reason.to_string() });
+    }
+    Ok(exclusions)
+}
+
+/// Moves the epochs inside any of the exclusions out of `sensor_data`.
+/// The removed epochs are returned grouped by day, with the reason set, so they
+/// can still be counted as excluded time. Days excluded entirely stay in
+/// `sensor_data` without epochs, so they still get a Daily row.
+pub fn apply(
+    sensor_data: &mut HashMap<NaiveDate, Vec<SensorEntry>>,
+    exclusions: &[Exclusion],
+) -> (HashMap<NaiveDate, Vec<SensorEntry>>, Vec<QualityIssue>) {
+    let mut excluded_data: HashMap<NaiveDate, Vec<SensorEntry>> = HashMap::new();
+    let mut counts = vec![0; exclusions.len()];
+
+    for (date, entries) in sensor_data.iter_mut() {
+        let mut kept = Vec::with_capacity(entries.len());
+        for mut entry in entries.drain(..) {
+            match exclusions.iter().position(|e| e.contains(entry.timestamp())) {
+                Some(i) => {
+                    counts[i] += 1;
+                    entry.exclusion = Some(exclusions[i].reason.clone());
+                    excluded_data.entry(*date).or_default().push(entry);
+                },
+                None => kept.push(entry),
+            }
+        }
+        *entries = kept;
+    }
+
+    let issues = exclusions
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(exclusion, count)| QualityIssue {
+            kind: IssueKind::Excluded,
+            timestamp: exclusion.start,
+            detail: format!("{}: {} epoch(s) until {}", exclusion.reason, count, exclusion.end),
+            action: "Excluded".to_string(),
+        })
+        .collect();
+
+    (excluded_data, issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn intervals_need_an_end_after_the_start() {
+        let exclusion = Exclusion::parse("2023-05-03 10:00, 2023-05-03 10:30, Vet visit, then a bath").unwrap();
+        assert_eq!(exclusion.reason, "Vet visit, then a bath");
+        assert!(exclusion.contains(exclusion.start));
+        assert!(!exclusion.contains(exclusion.end));
+        assert_eq!(Exclusion::parse("2023-05-03 10:00, 2023-05-03 10:30").unwrap().reason, "Excluded");
+        assert!(Exclusion::parse("2023-05-03 10:30, 2023-05-03 10:00, Bath").is_none());
+        assert!(Exclusion::parse("2023-05-03 10:30").is_none());
+    }
+
+    #[test]
+    fn excluded_epochs_are_moved_out() {
+        let mut sensor_data = testing::by_day(testing::entries("2023-05-03 10:00", &[1, 2, 3, 4, 5]));
+        let exclusions = [Exclusion::parse("2023-05-03 10:00:15, 2023-05-03 10:00:45, bath").unwrap()];
+
+        let (excluded, issues) = apply(&mut sensor_data, &exclusions);
+        let values = |data: &HashMap<NaiveDate, Vec<SensorEntry>>| data.values().flatten().map(|e| e.value).collect::<Vec<_>>();
+        assert_eq!(values(&sensor_data), [1, 4, 5]);
+        assert_eq!(values(&excluded), [2, 3]);
+        assert!(excluded.values().flatten().all(|e| e.exclusion.as_deref() == Some("bath")));
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].detail.starts_with("bath: 2 epoch(s)"));
+    }
+
+    #[test]
+    fn excluded_days_are_kept() {
+        let mut sensor_data = testing::by_day(testing::entries("2023-05-03 23:59:30", &[1, 2, 3, 4]));
+        let exclusions = [Exclusion::parse("2023-05-04 00:00, 2023-05-05 00:00, Vet visit").unwrap()];
+
+        let (excluded, _) = apply(&mut sensor_data, &exclusions);
+        let day = NaiveDate::from_ymd_opt(2023, 5, 4).unwrap();
+        assert!(sensor_data[&day].is_empty());
+        assert_eq!(excluded[&day].len(), 2);
+        assert_eq!(sensor_data.len(), 2);
+    }
+}
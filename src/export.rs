@@ -0,0 +1,183 @@
+use chrono::NaiveDate;
+
+use crate::{aggregate::Summary, metrics::{Metric, MetricRegistry}};
+
+const TIDY_COLUMNS: [&str; 8] = ["dog", "level", "period", "date", "days", "metric", "unit", "value"];
+
+/// One row of the long format export: a single metric value of a day, or its
+/// average over a week, weekend or weekdays.
+#[derive(Debug, Clone)]
+pub struct TidyRow<'a> {
+    pub dog: &'a str,
+    /// `day`, `week`, `weekend` or `weekdays`.
+    pub level: &'static str,
+    /// Day or calendar week of the recording, both one-based.
+    pub period: usize,
+    /// The day, or the first day of the averaged ones.
+    pub date: NaiveDate,
+    /// Number of days averaged.
+    pub days: usize,
+    pub metric: &'a str,
+    pub unit: &'static str,
+    /// Raw value, durations are in seconds.
+    pub value: f64,
+}
+
+/// Every value of the Daily and Weekly sheets, one row per dog, day (or week)
+/// and metric.
+pub fn tidy_rows<'a>(dog: &'a str, summary: &Summary, metrics: &'a MetricRegistry) -> Vec<TidyRow<'a>> {
+    let mut rows = vec![];
+    for day in summary.days.iter() {
+        for (metric, value) in defined(metrics, &day.values) {
+            rows.push(TidyRow {
+                dog,
+                level: "day",
+                period: day.number,
+                date: day.date,
+                days: 1,
+                metric: metric.id(),
+                unit: metric.unit().label(),
+                value,
+            });
+        }
+    }
+    for period in summary.periods.iter() {
+        for (metric, value) in defined(metrics, &period.values) {
+            rows.push(TidyRow {
+                dog,
+                level: period.kind.id(),
+                period: period.number,
+                date: period.days[0].date,
+                days: period.days.len(),
+                metric: metric.id(),
+                unit: metric.unit().label(),
+                value,
+            });
+        }
+    }
+    rows
+}
+
+/// The metrics with a value, undefined ones get no row.
+fn defined<'a>(metrics: &'a MetricRegistry, values: &[Option<f64>]) -> Vec<(&'a dyn Metric, f64)> {
+    metrics.metrics().iter().zip(values.iter()).filter_map(|(metric, value)| Some((metric.as_ref(), (*value)?))).collect()
+}
+
+pub fn write_tidy_csv(path: &str, rows: &[TidyRow]) -> Result<(), String> {
+    match write_csv(path, rows) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Can't write tidy export {}.\nSource: {}", path, e)),
+    }
+}
+
+fn write_csv(path: &str, rows: &[TidyRow]) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(TIDY_COLUMNS)?;
+    for row in rows.iter() {
+        writer.write_record([
+            row.dog.to_string(),
+            row.level.to_string(),
+            row.period.to_string(),
+            row.date.format("%Y-%m-%d").to_string(),
+            row.days.to_string(),
+            row.metric.to_string(),
+            row.unit.to_string(),
+            row.value.to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+pub fn write_tidy_parquet(path: &str, rows: &[TidyRow]) -> Result<(), String> {
+    use std::{fs::File, sync::Arc};
+
+    use parquet::{
+        data_type::{ByteArray, ByteArrayType, DoubleType, Int32Type},
+        file::writer::SerializedFileWriter,
+        schema::parser::parse_message_type,
+    };
+
+    let schema = "
+        message tidy {
+            REQUIRED BYTE_ARRAY dog (UTF8);
+            REQUIRED BYTE_ARRAY level (UTF8);
+            REQUIRED INT32 period;
+            REQUIRED INT32 date (DATE);
+            REQUIRED INT32 days;
+            REQUIRED BYTE_ARRAY metric (UTF8);
+            REQUIRED BYTE_ARRAY unit (UTF8);
+            REQUIRED DOUBLE value;
+        }
+    ";
+    let error = |e: parquet::errors::ParquetError| format!("Can't write tidy export {}.\nSource: {}", path, e);
+
+    let schema = Arc::new(parse_message_type(schema).map_err(error)?);
+    let file = match File::create(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Can't create tidy export {}.\nSource: {}", path, e)),
+    };
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+    let dogs: Vec<ByteArray> = rows.iter().map(|r| ByteArray::from(r.dog)).collect();
+    let levels: Vec<ByteArray> = rows.iter().map(|r| ByteArray::from(r.level)).collect();
+    let periods: Vec<i32> = rows.iter().map(|r| r.period as i32).collect();
+    let dates: Vec<i32> = rows.iter().map(|r| (r.date - epoch).num_days() as i32).collect();
+    let days: Vec<i32> = rows.iter().map(|r| r.days as i32).collect();
+    let metrics: Vec<ByteArray> = rows.iter().map(|r| ByteArray::from(r.metric)).collect();
+    let units: Vec<ByteArray> = rows.iter().map(|r| ByteArray::from(r.unit)).collect();
+    let values: Vec<f64> = rows.iter().map(|r| r.value).collect();
+
+    let mut writer = SerializedFileWriter::new(file, schema, Default::default()).map_err(error)?;
+    let mut row_group = writer.next_row_group().map_err(error)?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column().map_err(error)? {
+        let written = match TIDY_COLUMNS[index] {
+            "dog"       => column.typed::<ByteArrayType>().write_batch(&dogs, None, None),
+            "level"     => column.typed::<ByteArrayType>().write_batch(&levels, None, None),
+            "period"    => column.typed::<Int32Type>().write_batch(&periods, None, None),
+            "date"      => column.typed::<Int32Type>().write_batch(&dates, None, None),
+            "days"      => column.typed::<Int32Type>().write_batch(&days, None, None),
+            "metric"    => column.typed::<ByteArrayType>().write_batch(&metrics, None, None),
+            "unit"      => column.typed::<ByteArrayType>().write_batch(&units, None, None),
+            _           => column.typed::<DoubleType>().write_batch(&values, None, None),
+        };
+        written.map_err(error)?;
+        column.close().map_err(error)?;
+        index += 1;
+    }
+    row_group.close().map_err(error)?;
+    writer.close().map_err(error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn every_defined_value_gets_a_row() {
+        let config = testing::config("[metrics]\nenabled=tot_counts, ave_counts_min");
+        let metrics = MetricRegistry::from_config(&config).unwrap();
+        let mut entries = testing::entries("2023-05-05 10:00", &[10, 20]);
+        entries.extend(testing::entries("2023-05-06 10:00", &[-1, -1]));
+        entries.extend(testing::entries("2023-05-08 10:00", &[600]));
+        let summary = Summary::compute(&testing::analysis(entries), &metrics, &config);
+
+        // the empty Saturday has no average, nor has the weekend of the first week
+        let rows = tidy_rows("billy", &summary, &metrics);
+        let count = |level: &str| rows.iter().filter(|r| r.level == level).count();
+        assert_eq!((count("day"), count("week"), count("weekend"), count("weekdays")), (5, 4, 1, 4));
+        assert!(!rows.iter().any(|r| r.level == "weekend" && r.metric == "ave_counts_min"));
+
+        let path = std::env::temp_dir().join("every_defined_value_gets_a_row.csv");
+        write_tidy_csv(path.to_str().unwrap(), &rows).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], TIDY_COLUMNS.join(","));
+        assert_eq!(lines[1], "billy,day,1,2023-05-05,1,tot_counts,counts,30");
+        assert_eq!(lines[6], "billy,week,1,2023-05-05,2,tot_counts,counts,15");
+        assert_eq!(lines.len(), rows.len() + 1);
+    }
+}
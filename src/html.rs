@@ -0,0 +1,320 @@
+use std::fmt::Write;
+
+use crate::{
+    aggregate::{self, DayValues, Summary},
+    config::Config,
+    i18n::Translations,
+    intensity::Intensity,
+    metrics::{MetricRegistry, Unit},
+    plots::{self, escape},
+    weeks, Analysis,
+};
+
+const DATE_FORMAT: &str = "%d.%m.%Y";
+
+const STYLE: &str = "body { font-family: 'DejaVu Sans', Arial, sans-serif; max-width: 960px; margin: 2em auto; color: #222; }
+h1 { font-size: 1.6em; }
+h2 { font-size: 1.2em; margin-top: 1.6em; border-bottom: 1px solid #ccc; }
+table { border-collapse: collapse; }
+th, td { padding: 4px 12px; border-bottom: 1px solid #eee; text-align: left; }
+td.number { text-align: right; }
+.met { color: #1a7f37; }
+.missed { color: #b42318; }
+svg { max-width: 100%; height: auto; }";
+
+/// Owner report with everything inlined, so the single file can be sent by
+/// mail. Written from the same daily values as the workbook.
+pub fn write_report(
+    path: &str,
+    analysis: &Analysis,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    translations: &Translations,
+    config: &Config,
+) -> Result<(), String> {
+    let html = build(analysis, summary, metrics, translations, config)?;
+    match std::fs::write(path, html) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Can't write HTML report {}.\nSource: {}", path, e)),
+    }
+}
+
+fn build(
+    analysis: &Analysis,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    translations: &Translations,
+    config: &Config,
+) -> Result<String, String> {
+    let targeted = targeted_metrics(metrics, config)?;
+
+    let mut html = String::new();
+    let title = format!("{}: {}", translations.get("html.title"), config.dog);
+    let _ = writeln!(html, "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">", escape(config.language.as_deref().unwrap_or("en")));
+    let _ = writeln!(html, "<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>", escape(&title), STYLE);
+    let _ = writeln!(html, "<h1>{}</h1>", escape(&title));
+
+    let _ = writeln!(html, "<h2>{}</h2>\n<table>", escape(translations.get("html.profile")));
+    let mut profile = vec![(translations.get("label.dog").to_string(), config.dog.clone())];
+    if let Some(metadata) = &analysis.metadata {
+        profile.extend(metadata.fields.iter().filter(|(_, v)| !v.is_empty()).cloned());
+    }
+    if let (Some(first), Some(last)) = (summary.days.first(), summary.days.last()) {
+        profile.push((
+            translations.get("label.recording").to_string(),
+            format!("{} – {}", first.date.format(DATE_FORMAT), last.date.format(DATE_FORMAT)),
+        ));
+    }
+    profile.push((translations.get("label.days_analysed").to_string(), summary.days.len().to_string()));
+    profile.push((translations.get("label.epoch").to_string(), format!("{} s", config.epoch_seconds)));
+    for (name, value) in profile.iter() {
+        let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", escape(name), escape(value));
+    }
+    html.push_str("</table>\n");
+
+    let _ = writeln!(html, "<h2>{}</h2>", escape(translations.get("html.daily")));
+    html.push_str(&plots::daily_bars_svg(summary, metrics, translations));
+
+    let _ = writeln!(html, "<h2>{}</h2>\n<table>", escape(translations.get("chart.weekday_weekend")));
+    let _ = writeln!(
+        html,
+        "<tr><th></th><th>{}</th><th>{}</th></tr>",
+        escape(translations.get("label.weekdays")),
+        escape(translations.get("label.weekend")),
+    );
+    for index in metrics.band_positions() {
+        let weekdays = aggregate::mean(&summary.days, index, |d| !weeks::is_weekend(&d.date));
+        let weekend = aggregate::mean(&summary.days, index, |d| weeks::is_weekend(&d.date));
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td class=\"number\">{}</td><td class=\"number\">{}</td></tr>",
+            escape(metric_name(metrics, translations, index)),
+            weekdays.map(minutes).unwrap_or_default(),
+            weekend.map(minutes).unwrap_or_default(),
+        );
+    }
+    html.push_str("</table>\n");
+
+    let _ = writeln!(html, "<h2>{}</h2>\n<table>", escape(translations.get("html.targets")));
+    let _ = writeln!(
+        html,
+        "<tr><th></th><th>{}</th><th>{}</th><th>{}</th></tr>",
+        escape(translations.get("column.average")),
+        escape(translations.get("column.target")),
+        escape(translations.get("column.days_met")),
+    );
+    for index in targeted.iter() {
+        let id = metrics.metrics()[*index].id();
+        let average = aggregate::mean(&summary.days, *index, |_| true);
+        let target = target_text(id, translations, config);
+        let met = match target_met(summary, *index, id, config) {
+            Some(met) => {
+                let class = if met * 2 >= summary.days.len() { "met" } else { "missed" };
+                format!("<span class=\"{}\">{} / {}</span>", class, met, summary.days.len())
+            },
+            None => "–".to_string(),
+        };
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td class=\"number\">{}</td><td>{}</td><td>{}</td></tr>",
+            escape(metric_name(metrics, translations, *index)),
+            average.map(minutes).unwrap_or_default(),
+            escape(&target),
+            met,
+        );
+    }
+    html.push_str("</table>\n");
+
+    let _ = writeln!(html, "<h2>{}</h2>\n<ul>", escape(translations.get("html.notes")));
+    for note in notes(analysis, summary, metrics, translations, config, &targeted) {
+        let _ = writeln!(html, "<li>{}</li>", escape(&note));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    Ok(html)
+}
+
+/// The intensity metrics and every other metric with a target, in registry order.
+fn targeted_metrics(metrics: &MetricRegistry, config: &Config) -> Result<Vec<usize>, String> {
+    for id in config.target_min.keys().chain(config.target_max.keys()) {
+        match metrics.position(id).map(|i| metrics.metrics()[i].unit()) {
+            Some(Unit::Duration) => (),
+            Some(_) => return Err(format!("Can't set a target for metric \"{}\" in the [targets] section of config.ini. Targets are minutes, only time metrics can have one", id)),
+            None => return Err(format!("Can't find metric \"{}\" from the [targets] section of config.ini. It must be an enabled metric", id)),
+        }
+    }
+    Ok(metrics
+        .metrics()
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| {
+            m.band().is_some()
+                || config.target_min.contains_key(m.id())
+                || config.target_max.contains_key(m.id())
+        })
+        .map(|(i, _)| i)
+        .collect())
+}
+
+fn target_text(id: &str, translations: &Translations, config: &Config) -> String {
+    match (config.target_min.get(id), config.target_max.get(id)) {
+        (Some(min), Some(max)) => format!("{:.0} – {:.0} min", min, max),
+        (Some(min), None) => fill(translations.get("target.min"), &[("minutes", format!("{:.0}", min))]),
+        (None, Some(max)) => fill(translations.get("target.max"), &[("minutes", format!("{:.0}", max))]),
+        (None, None) => "–".to_string(),
+    }
+}
+
+/// On how many days the target was met, `None` without a target.
+fn target_met(summary: &Summary, index: usize, id: &str, config: &Config) -> Option<usize> {
+    let min = config.target_min.get(id).copied();
+    let max = config.target_max.get(id).copied();
+    if min.is_none() && max.is_none() {
+        return None;
+    }
+    let met = summary
+        .days
+        .iter()
+        .filter(|d| {
+            d.values[index].is_some_and(|value| {
+                let value = value / 60.;
+                min.is_none_or(|m| value >= m) && max.is_none_or(|m| value <= m)
+            })
+        })
+        .count();
+    Some(met)
+}
+
+fn notes(
+    analysis: &Analysis,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    translations: &Translations,
+    config: &Config,
+    targeted: &[usize],
+) -> Vec<String> {
+    let mut notes = vec![];
+    let dog = config.dog.clone();
+
+    // minutes in the bands counted as active in bouts, when all of them are enabled
+    let active_band = &config.bands[config.bout_band];
+    let active_positions: Option<Vec<usize>> = config.bands[config.bout_band..].iter().map(|b| metrics.position(&b.metric)).collect();
+    if let (Some(positions), false) = (active_positions, summary.days.is_empty()) {
+        let active = |d: &DayValues| positions.iter().map(|i| d.values[*i].unwrap_or_default()).sum::<f64>() / 60.;
+        let average = summary.days.iter().map(active).sum::<f64>() / summary.days.len() as f64;
+        notes.push(fill(
+            translations.get("note.active"),
+            &[
+                ("dog", dog.clone()),
+                ("intensity", translations.intensity(Intensity::Band(active_band)).to_lowercase()),
+                ("minutes", format!("{:.0}", average)),
+            ],
+        ));
+
+        let most = summary.days.iter().max_by(|a, b| active(a).total_cmp(&active(b)));
+        let least = summary.days.iter().min_by(|a, b| active(a).total_cmp(&active(b)));
+        if let (Some(most), Some(least), true) = (most, least, summary.days.len() > 1) {
+            notes.push(fill(
+                translations.get("note.extremes"),
+                &[
+                    ("max_date", most.date.format(DATE_FORMAT).to_string()),
+                    ("max", format!("{:.0}", active(most))),
+                    ("min_date", least.date.format(DATE_FORMAT).to_string()),
+                    ("min", format!("{:.0}", active(least))),
+                ],
+            ));
+        }
+
+        let weekend: Vec<f64> = summary.days.iter().filter(|d| weeks::is_weekend(&d.date)).map(active).collect();
+        let weekdays: Vec<f64> = summary.days.iter().filter(|d| !weeks::is_weekend(&d.date)).map(active).collect();
+        if !weekend.is_empty() && !weekdays.is_empty() {
+            let difference = weekend.iter().sum::<f64>() / weekend.len() as f64
+                - weekdays.iter().sum::<f64>() / weekdays.len() as f64;
+            let key = if difference >= 0. { "note.weekend_more" } else { "note.weekend_less" };
+            notes.push(fill(translations.get(key), &[("dog", dog.clone()), ("minutes", format!("{:.0}", difference.abs()))]));
+        }
+    }
+
+    for index in targeted.iter() {
+        let id = metrics.metrics()[*index].id();
+        if let Some(met) = target_met(summary, *index, id, config) {
+            notes.push(fill(
+                translations.get("note.target_met"),
+                &[
+                    ("metric", metric_name(metrics, translations, *index).to_lowercase()),
+                    ("met", met.to_string()),
+                    ("days", summary.days.len().to_string()),
+                ],
+            ));
+        }
+    }
+
+    match analysis.quality_issues.len() {
+        0 => notes.push(translations.get("note.no_issues").to_string()),
+        count => notes.push(fill(translations.get("note.quality"), &[("count", count.to_string())])),
+    }
+    notes
+}
+
+fn metric_name<'a>(metrics: &'a MetricRegistry, translations: &'a Translations, index: usize) -> &'a str {
+    let metric = &metrics.metrics()[index];
+    translations.column(metric.id(), metric.name())
+}
+
+/// Seconds as whole minutes.
+fn minutes(seconds: f64) -> String {
+    format!("{:.0} min", seconds / 60.)
+}
+
+/// Replaces every `{name}` placeholder of a translated sentence.
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    let mut text = template.to_string();
+    for (name, value) in values.iter() {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn active_minutes_follow_the_configured_bands() {
+        let config = testing::config("[format]\nlanguage=en\n[bands]\nrest=0\nlight=50\nbrisk=500\nhard=2000");
+        let metrics = MetricRegistry::from_config(&config).unwrap();
+        let analysis = testing::analysis(testing::entries("2023-05-03 10:00", &[[10; 4], [100; 4], [600; 4], [2500; 4]].concat()));
+        let summary = Summary::compute(&analysis, &metrics, &config);
+
+        let notes = notes(&analysis, &summary, &metrics, &Translations::from_config(&config).unwrap(), &config, &[]);
+        assert!(notes.contains(&"On average test was active at light intensity or above for 3 minutes a day.".to_string()), "{:?}", notes);
+    }
+
+    #[test]
+    fn reports_show_the_metrics_and_their_targets() {
+        let config = testing::config("[format]\nlanguage=en\n[targets]\nmin_total_vig=1\nmax_t_non_zero=1");
+        let metrics = MetricRegistry::from_config(&config).unwrap();
+        let translations = Translations::from_config(&config).unwrap();
+        let mut entries = testing::entries("2023-05-05 10:00", &[1200; 4]);
+        entries.extend(testing::entries("2023-05-06 10:00", &[1200; 5]));
+        let analysis = testing::analysis(entries);
+        let summary = Summary::compute(&analysis, &metrics, &config);
+
+        let html = build(&analysis, &summary, &metrics, &translations, &config).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"en\">"));
+        for index in metrics.band_positions() {
+            assert!(html.contains(&format!("<tr><th>{}</th>", escape(metric_name(&metrics, &translations, index)))));
+        }
+        let non_zero = metrics.position("t_non_zero").unwrap();
+        assert!(html.contains(&format!("<tr><th>{}</th>", escape(metric_name(&metrics, &translations, non_zero)))));
+        // at least a minute of vigorous activity on both days, at most a minute
+        // of activity only on the first one
+        assert!(html.contains("<span class=\"met\">2 / 2</span>"));
+        assert!(html.contains("<span class=\"met\">1 / 2</span>"));
+        assert!(html.contains("<svg"));
+
+        let counts = testing::config("[targets]\nmin_tot_counts=100");
+        let error = build(&analysis, &summary, &MetricRegistry::from_config(&counts).unwrap(), &translations, &counts).unwrap_err();
+        assert!(error.contains("\"tot_counts\""), "{}", error);
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+
+use chrono::Weekday;
+use configparser::ini::Ini;
+
+use crate::{config::Config, continuity::IssueKind, diary::NO_EVENT, intensity::Intensity};
+
+/// Strings every report is written with. Metric headers are missing here, they
+/// default to the metric names.
+const ENGLISH: [(&str, &str); 150] = [
+    ("sheet.daily", "Daily"),
+    ("sheet.weekly", "Weekly"),
+    ("sheet.quality", "Data quality"),
+    ("sheet.events", "Events"),
+    ("sheet.epochs", "Epochs"),
+    ("sheet.charts", "Charts"),
+    ("sheet.cohort", "Cohort"),
+    ("sheet.groups", "Groups"),
+    ("sheet.statistics", "Statistics"),
+    ("sheet.prepost", "Pre-post"),
+    ("sheet.windows", "Windows"),
+    ("sheet.circadian", "Circadian"),
+    ("sheet.cosinor", "Cosinor"),
+    ("sheet.distribution", "Minutes per intensity"),
+    ("chart.intensity", "Daily intensity"),
+    ("chart.counts", "Daily total counts"),
+    ("chart.weekday_weekend", "Weekdays vs weekend"),
+    ("chart.hourly", "Average hourly profile"),
+    ("chart.weekly_counts", "Weekly total counts"),
+    ("plot.actogram", "Actogram"),
+    ("html.title", "Activity report"),
+    ("html.profile", "Profile"),
+    ("html.daily", "Daily activity"),
+    ("html.targets", "Time in intensity vs targets"),
+    ("html.notes", "Notes"),
+    ("label.dog", "Dog"),
+    ("label.recording", "Recording"),
+    ("label.days_analysed", "Days analysed"),
+    ("label.epoch", "Epoch length"),
+    ("column.average", "Average per day"),
+    ("column.target", "Target"),
+    ("column.days_met", "Days on target"),
+    ("target.min", "at least {minutes} min"),
+    ("target.max", "at most {minutes} min"),
+    ("note.active", "On average {dog} was active at {intensity} intensity or above for {minutes} minutes a day."),
+    ("note.extremes", "The most active day was {max_date} ({max} min), the least active {min_date} ({min} min)."),
+    ("note.weekend_more", "On weekends {dog} was active {minutes} minutes a day more than on weekdays."),
+    ("note.weekend_less", "On weekends {dog} was active {minutes} minutes a day less than on weekdays."),
+    ("note.target_met", "The target for {metric} time was met on {met} of {days} days."),
+    ("note.quality", "{count} data quality issues were found, results of the affected days may be less accurate."),
+    ("note.no_issues", "No data quality issues were found."),
+    ("axis.date", "Date"),
+    ("axis.week", "Week"),
+    ("axis.hour", "Hour"),
+    ("axis.duration", "Time (h:mm)"),
+    ("axis.minutes", "Minutes per day"),
+    ("axis.counts", "Counts"),
+    ("axis.counts_epoch", "Counts/epoch"),
+    ("column.day", "Day"),
+    ("column.date", "Date"),
+    ("column.weekday", "Weekday"),
+    ("column.label", "Label"),
+    ("column.days", "Days"),
+    ("column.dates", "Dates"),
+    ("column.type", "Type"),
+    ("column.time", "Time"),
+    ("column.details", "Details"),
+    ("column.action", "Action"),
+    ("column.event", "Event"),
+    ("column.occurrences", "Occurrences"),
+    ("column.duration", "Time"),
+    ("column.mag_value", "Mag. Value"),
+    ("column.intensity", "Intensity"),
+    ("column.non_wear", "Non-wear"),
+    ("column.bout", "Bout"),
+    ("column.exclusion", "Exclusion"),
+    ("column.dog", "Dog"),
+    ("column.valid_days", "Valid days"),
+    ("column.field", "Field"),
+    ("column.group", "Group"),
+    ("column.metric", "Metric"),
+    ("column.n", "N"),
+    ("column.mean", "Mean"),
+    ("column.sd", "SD"),
+    ("column.median", "Median"),
+    ("column.q1", "Q1"),
+    ("column.q3", "Q3"),
+    ("column.iqr", "IQR"),
+    ("column.group_a", "Group A"),
+    ("column.group_b", "Group B"),
+    ("column.n_a", "N A"),
+    ("column.mean_a", "Mean A"),
+    ("column.sd_a", "SD A"),
+    ("column.n_b", "N B"),
+    ("column.mean_b", "Mean B"),
+    ("column.sd_b", "SD B"),
+    ("column.difference", "Difference"),
+    ("column.t", "t"),
+    ("column.df", "df"),
+    ("column.p_t", "p (t-test)"),
+    ("column.hedges_g", "Hedges' g"),
+    ("column.u", "U"),
+    ("column.z", "z"),
+    ("column.p_u", "p (Mann-Whitney)"),
+    ("column.r", "r (rank-biserial)"),
+    ("column.baseline", "Baseline"),
+    ("column.followup", "Follow-up"),
+    ("column.pairs", "Pairs"),
+    ("column.mean_baseline", "Mean baseline"),
+    ("column.mean_followup", "Mean follow-up"),
+    ("column.mean_difference", "Mean difference"),
+    ("column.sd_difference", "SD of differences"),
+    ("column.dz", "Cohen's dz"),
+    ("column.hours", "Hours"),
+    ("column.is", "IS"),
+    ("column.iv", "IV"),
+    ("column.ra", "RA"),
+    ("column.l5", "L5 (counts/min)"),
+    ("column.l5_onset", "L5 onset"),
+    ("column.m10", "M10 (counts/min)"),
+    ("column.m10_onset", "M10 onset"),
+    ("column.model", "Model"),
+    ("column.mesor", "MESOR (counts/min)"),
+    ("column.amplitude", "Amplitude (counts/min)"),
+    ("column.acrophase", "Acrophase"),
+    ("column.ci_low", "95% CI low"),
+    ("column.ci_high", "95% CI high"),
+    ("column.r_squared", "R²"),
+    ("column.p", "p"),
+    ("weekday.mon", "Mon"),
+    ("weekday.tue", "Tue"),
+    ("weekday.wed", "Wed"),
+    ("weekday.thu", "Thu"),
+    ("weekday.fri", "Fri"),
+    ("weekday.sat", "Sat"),
+    ("weekday.sun", "Sun"),
+    ("label.week", "Week"),
+    ("label.weekend", "Weekend"),
+    ("label.weekdays", "Weekdays"),
+    ("label.no_issues", "No issues found"),
+    ("label.no_event", NO_EVENT),
+    ("label.all_dogs", "All dogs"),
+    ("label.all_days", "All days"),
+    ("label.pre", "Pre"),
+    ("label.post", "Post"),
+    ("label.change", "Change"),
+    ("label.change_pct", "Change (%)"),
+    ("label.group_comparison", "Group comparisons"),
+    ("label.paired_comparison", "Baseline vs follow-up"),
+    ("issue.gap", "Gap"),
+    ("issue.duplicate", "Duplicate"),
+    ("issue.out_of_order", "Out of order"),
+    ("issue.epoch_length", "Epoch length"),
+    ("issue.excluded", "Excluded"),
+    ("issue.open_event", "Open event"),
+    ("intensity.non_wear", "Non-wear"),
+    ("intensity.sedentary", "Sedentary"),
+    ("intensity.low", "Low"),
+    ("intensity.moderate", "Moderate"),
+    ("intensity.vigorus", "Vigorous"),
+];
+
+/// Labels the Weekly sheet had before the reports were translated, kept when
+/// no language is set.
+const ORIGINAL: [(&str, &str); 2] = [
+    ("label.week", "Teden"),
+    ("label.weekend", "Vikend"),
+];
+
+const SLOVENIAN: [(&str, &str); 163] = [
+    ("sheet.daily", "Dnevno"),
+    ("sheet.weekly", "Tedensko"),
+    ("sheet.quality", "Kakovost podatkov"),
+    ("sheet.events", "Dogodki"),
+    ("sheet.epochs", "Epohe"),
+    ("sheet.charts", "Grafi"),
+    ("sheet.cohort", "Kohorta"),
+    ("sheet.groups", "Skupine"),
+    ("sheet.statistics", "Statistika"),
+    ("sheet.prepost", "Pred-po"),
+    ("sheet.windows", "Okna"),
+    ("sheet.circadian", "Cirkadiani ritem"),
+    ("sheet.cosinor", "Kosinor"),
+    ("sheet.distribution", "Minute po intenzivnosti"),
+    ("chart.intensity", "Dnevna intenzivnost"),
+    ("chart.counts", "Dnevno skupaj štetja"),
+    ("chart.weekday_weekend", "Delovni dnevi in vikend"),
+    ("chart.hourly", "Povprečni urni profil"),
+    ("chart.weekly_counts", "Tedensko skupaj štetja"),
+    ("plot.actogram", "Aktogram"),
+    ("html.title", "Poročilo o aktivnosti"),
+    ("html.profile", "Profil"),
+    ("html.daily", "Dnevna aktivnost"),
+    ("html.targets", "Čas po intenzivnosti in cilji"),
+    ("html.notes", "Opombe"),
+    ("label.dog", "Pes"),
+    ("label.recording", "Snemanje"),
+    ("label.days_analysed", "Analizirani dnevi"),
+    ("label.epoch", "Dolžina epohe"),
+    ("column.average", "Povprečje na dan"),
+    ("column.target", "Cilj"),
+    ("column.days_met", "Dnevi v cilju"),
+    ("target.min", "vsaj {minutes} min"),
+    ("target.max", "največ {minutes} min"),
+    ("note.active", "{dog} je bil v povprečju aktiven {minutes} minut na dan (intenzivnost {intensity} ali več)."),
+    ("note.extremes", "Najbolj aktiven dan je bil {max_date} ({max} min), najmanj aktiven {min_date} ({min} min)."),
+    ("note.weekend_more", "Ob vikendih je bil {dog} aktiven {minutes} minut na dan več kot med tednom."),
+    ("note.weekend_less", "Ob vikendih je bil {dog} aktiven {minutes} minut na dan manj kot med tednom."),
+    ("note.target_met", "Cilj za čas ({metric}) je bil dosežen v {met} od {days} dni."),
+    ("note.quality", "Najdenih je bilo {count} težav s kakovostjo podatkov, rezultati prizadetih dni so lahko manj natančni."),
+    ("note.no_issues", "Težav s kakovostjo podatkov ni bilo."),
+    ("axis.date", "Datum"),
+    ("axis.week", "Teden"),
+    ("axis.hour", "Ura"),
+    ("axis.duration", "Čas (h:mm)"),
+    ("axis.minutes", "Minute na dan"),
+    ("axis.counts", "Štetja"),
+    ("axis.counts_epoch", "Štetja/epoho"),
+    ("column.day", "Dan"),
+    ("column.date", "Datum"),
+    ("column.weekday", "Dan v tednu"),
+    ("column.label", "Oznaka"),
+    ("column.days", "Dni"),
+    ("column.dates", "Datumi"),
+    ("column.type", "Vrsta"),
+    ("column.time", "Čas"),
+    ("column.details", "Podrobnosti"),
+    ("column.action", "Ukrep"),
+    ("column.event", "Dogodek"),
+    ("column.occurrences", "Ponovitve"),
+    ("column.duration", "Čas"),
+    ("column.mag_value", "Mag. vrednost"),
+    ("column.intensity", "Intenzivnost"),
+    ("column.non_wear", "Nenošenje"),
+    ("column.bout", "Niz"),
+    ("column.exclusion", "Izključitev"),
+    ("column.dog", "Pes"),
+    ("column.valid_days", "Veljavni dnevi"),
+    ("column.field", "Polje"),
+    ("column.group", "Skupina"),
+    ("column.metric", "Metrika"),
+    ("column.n", "N"),
+    ("column.mean", "Povprečje"),
+    ("column.sd", "SO"),
+    ("column.median", "Mediana"),
+    ("column.q1", "Q1"),
+    ("column.q3", "Q3"),
+    ("column.iqr", "IQR"),
+    ("column.group_a", "Skupina A"),
+    ("column.group_b", "Skupina B"),
+    ("column.n_a", "N A"),
+    ("column.mean_a", "Povprečje A"),
+    ("column.sd_a", "SO A"),
+    ("column.n_b", "N B"),
+    ("column.mean_b", "Povprečje B"),
+    ("column.sd_b", "SO B"),
+    ("column.difference", "Razlika"),
+    ("column.t", "t"),
+    ("column.df", "df"),
+    ("column.p_t", "p (t-test)"),
+    ("column.hedges_g", "Hedgesov g"),
+    ("column.u", "U"),
+    ("column.z", "z"),
+    ("column.p_u", "p (Mann-Whitney)"),
+    ("column.r", "r (rangovno biserialni)"),
+    ("column.baseline", "Izhodišče"),
+    ("column.followup", "Kontrola"),
+    ("column.pairs", "Pari"),
+    ("column.mean_baseline", "Povprečje izhodišča"),
+    ("column.mean_followup", "Povprečje kontrole"),
+    ("column.mean_difference", "Povprečna razlika"),
+    ("column.sd_difference", "SO razlik"),
+    ("column.dz", "Cohenov dz"),
+    ("column.hours", "Ure"),
+    ("column.is", "IS"),
+    ("column.iv", "IV"),
+    ("column.ra", "RA"),
+    ("column.l5", "L5 (štetja/min)"),
+    ("column.l5_onset", "Začetek L5"),
+    ("column.m10", "M10 (štetja/min)"),
+    ("column.m10_onset", "Začetek M10"),
+    ("column.model", "Model"),
+    ("column.mesor", "MESOR (štetja/min)"),
+    ("column.amplitude", "Amplituda (štetja/min)"),
+    ("column.acrophase", "Akrofaza"),
+    ("column.ci_low", "95 % IZ spodaj"),
+    ("column.ci_high", "95 % IZ zgoraj"),
+    ("column.r_squared", "R²"),
+    ("column.p", "p"),
+    ("column.total_vig", "Živahna"),
+    ("column.total_mod", "Zmerna"),
+    ("column.total_low", "Nizka"),
+    ("column.total_sed", "Sedeča"),
+    ("column.t_non_zero", "Č. neničelno"),
+    ("column.t_zero", "Č. ničelno"),
+    ("column.t_empty", "Č. prazno"),
+    ("column.t_excluded", "Č. izključeno"),
+    ("column.tot_counts", "Skupaj štetja"),
+    ("column.ave_counts_min", "Povp. štetja/min"),
+    ("column.ave_counts_epoch", "Povp. štetja/epoho"),
+    ("column.ig_gradient", "Gradient IG"),
+    ("column.ig_intercept", "Presečišče IG"),
+    ("weekday.mon", "Pon"),
+    ("weekday.tue", "Tor"),
+    ("weekday.wed", "Sre"),
+    ("weekday.thu", "Čet"),
+    ("weekday.fri", "Pet"),
+    ("weekday.sat", "Sob"),
+    ("weekday.sun", "Ned"),
+    ("label.week", "Teden"),
+    ("label.weekend", "Vikend"),
+    ("label.weekdays", "Dnevi"),
+    ("label.no_issues", "Ni najdenih težav"),
+    ("label.no_event", "Brez dogodka"),
+    ("label.all_dogs", "Vsi psi"),
+    ("label.all_days", "Vsi dnevi"),
+    ("label.pre", "Pred"),
+    ("label.post", "Po"),
+    ("label.change", "Sprememba"),
+    ("label.change_pct", "Sprememba (%)"),
+    ("label.group_comparison", "Primerjave skupin"),
+    ("label.paired_comparison", "Izhodišče in kontrola"),
+    ("issue.gap", "Vrzel"),
+    ("issue.duplicate", "Dvojnik"),
+    ("issue.out_of_order", "Napačen vrstni red"),
+    ("issue.epoch_length", "Dolžina epohe"),
+    ("issue.excluded", "Izključeno"),
+    ("issue.open_event", "Dogodek brez konca"),
+    ("intensity.non_wear", "Nenošenje"),
+    ("intensity.sedentary", "Sedeča"),
+    ("intensity.low", "Nizka"),
+    ("intensity.moderate", "Zmerna"),
+    ("intensity.vigorus", "Živahna"),
+];
+
+/// Report strings in the configured language. Keys are `<group>.<name>`, e.g.
+/// `sheet.daily` or `column.tot_counts`.
+#[derive(Debug, Clone)]
+pub struct Translations {
+    strings: HashMap<String, String>,
+}
+
+impl Translations {
+    /// Built-in table of `language` (`en` or `sl`) with the strings of
+    /// `translation_file` on top. Missing strings fall back to English, without
+    /// a language the original labels are kept.
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut strings: HashMap<String, String> = ENGLISH.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        match config.language.as_deref() {
+            None => strings.extend(ORIGINAL.iter().map(|(k, v)| (k.to_string(), v.to_string()))),
+            Some("en") => {},
+            Some("sl") => strings.extend(SLOVENIAN.iter().map(|(k, v)| (k.to_string(), v.to_string()))),
+            Some(other) => return Err(format!("Can't parse \"language\" attribute in the [format] section of config.ini. Must be en or sl, not {}", other)),
+        }
+        if let Some(path) = &config.translation_file {
+            strings.extend(load_file(path)?);
+        }
+        Ok(Self { strings })
+    }
+
+    /// The string of `key`, or the key itself when no table has it.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).map(|s| s.as_str()).unwrap_or(key)
+    }
+
+    /// Header of a column, `default` when it isn't translated.
+    pub fn column<'a>(&'a self, id: &str, default: &'a str) -> &'a str {
+        self.strings.get(&format!("column.{}", id)).map(|s| s.as_str()).unwrap_or(default)
+    }
+
+    pub fn weekday(&self, weekday: Weekday) -> &str {
+        let key = match weekday {
+            Weekday::Mon => "weekday.mon",
+            Weekday::Tue => "weekday.tue",
+            Weekday::Wed => "weekday.wed",
+            Weekday::Thu => "weekday.thu",
+            Weekday::Fri => "weekday.fri",
+            Weekday::Sat => "weekday.sat",
+            Weekday::Sun => "weekday.sun",
+        };
+        self.get(key)
+    }
+
+    pub fn issue(&self, kind: IssueKind) -> &str {
+        let key = match kind {
+            IssueKind::Gap => "issue.gap",
+            IssueKind::Duplicate => "issue.duplicate",
+            IssueKind::OutOfOrder => "issue.out_of_order",
+            IssueKind::EpochMismatch => "issue.epoch_length",
+            IssueKind::Excluded => "issue.excluded",
+            IssueKind::OpenEvent => "issue.open_event",
+        };
+        self.get(key)
+    }
+
+    /// Label of an intensity, the band's own label when it isn't translated.
+    pub fn intensity<'a>(&'a self, intensity: Intensity<'a>) -> &'a str {
+        match intensity {
+            Intensity::NonWear => self.get("intensity.non_wear"),
+            Intensity::Band(band) => self.strings.get(&format!("intensity.{}", band.id)).map(|s| s.as_str()).unwrap_or(&band.label),
+        }
+    }
+
+    /// Diary event label, with the epochs outside any event translated.
+    pub fn event<'a>(&'a self, label: &'a str) -> &'a str {
+        if label == NO_EVENT {
+            self.get("label.no_event")
+        } else {
+            label
+        }
+    }
+}
+
+/// Reads a translation file. Sections are the key groups, e.g.
+///
+/// ```ini
+/// [sheet]
+/// daily=Täglich
+/// [column]
+/// tot_counts=Zählungen
+/// ```
+fn load_file(path: &str) -> Result<HashMap<String, String>, String> {
+    let file = match Ini::new().load(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Can't open translation file {}.\nSource: {}", path, e)),
+    };
+    let mut strings = HashMap::new();
+    for (group, values) in file.into_iter() {
+        for (name, value) in values.into_iter() {
+            if let Some(value) = value {
+                strings.insert(format!("{}.{}", group, name), value.trim().to_string());
+            }
+        }
+    }
+    Ok(strings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn reports_keep_their_original_labels_without_a_language() {
+        let original = Translations::from_config(&testing::config("")).unwrap();
+        assert_eq!((original.get("sheet.daily"), original.get("sheet.weekly")), ("Daily", "Weekly"));
+        assert_eq!((original.get("label.week"), original.get("label.weekend")), ("Teden", "Vikend"));
+        assert_eq!(original.weekday(Weekday::Mon), "Mon");
+
+        let english = Translations::from_config(&testing::config("[format]\nlanguage=EN")).unwrap();
+        assert_eq!(english.get("label.week"), "Week");
+    }
+
+    #[test]
+    fn translation_files_override_the_built_in_strings() {
+        let path = std::env::temp_dir().join("translation_files_override_the_built_in_strings.ini");
+        std::fs::write(&path, "[sheet]\ndaily=Täglich\n[column]\ntot_counts=Zählungen\n").unwrap();
+        let config = testing::config(&format!("[format]\nlanguage=en\ntranslation_file={}", path.display()));
+        let translations = Translations::from_config(&config).unwrap();
+
+        assert_eq!(translations.get("sheet.daily"), "Täglich");
+        assert_eq!(translations.column("tot_counts", "Tot Counts"), "Zählungen");
+        assert_eq!(translations.get("sheet.weekly"), "Weekly");
+        assert_eq!(translations.weekday(Weekday::Mon), "Mon");
+        assert_eq!(translations.column("m60", "M60"), "M60");
+        assert_eq!(translations.get("label.unknown"), "label.unknown");
+
+        let missing = testing::config("[format]\ntranslation_file=missing.ini");
+        assert!(Translations::from_config(&missing).unwrap_err().contains("missing.ini"));
+    }
+}
@@ -0,0 +1,138 @@
+/// Colors of the bands from the lowest to the highest, more bands get colors
+/// in between.
+const BAND_COLORS: [(u8, u8, u8); 4] = [(0xd9, 0xd9, 0xd9), (0xfd, 0xcc, 0x8a), (0xfc, 0x8d, 0x59), (0xd7, 0x30, 0x1f)];
+
+/// A named intensity band, from its cutpoint up to the next band's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Band {
+    /// Identifier used in the exports and translation keys, e.g. `moderate`.
+    pub id: String,
+    pub label: String,
+    /// Id of the daily time metric of the band, e.g. `total_mod`.
+    pub metric: String,
+    pub metric_name: String,
+    /// Lowest counts per epoch in the band.
+    pub cutpoint: i32,
+    pub color: String,
+}
+
+impl Band {
+    /// The sedentary, low, moderate and vigorus bands of the `[parsing]` cutpoints.
+    pub fn defaults(low: i32, moderate: i32, vigorus: i32) -> Vec<Band> {
+        let bands = [
+            ("sedentary", "Sedentary", "total_sed", "Total Sed.", 0),
+            ("low", "Low", "total_low", "Total Low", low),
+            ("moderate", "Moderate", "total_mod", "Total Mod.", moderate),
+            ("vigorus", "Vigorous", "total_vig", "Total Vig.", vigorus),
+        ];
+        bands
+            .iter()
+            .enumerate()
+            .map(|(i, (id, label, metric, metric_name, cutpoint))| Band {
+                id: id.to_string(),
+                label: label.to_string(),
+                metric: metric.to_string(),
+                metric_name: metric_name.to_string(),
+                cutpoint: *cutpoint,
+                color: color(i, bands.len()),
+            })
+            .collect()
+    }
+
+    /// Bands named by their ids, e.g. `very_vigorous`, ordered by cutpoint.
+    pub fn named(mut cutpoints: Vec<(String, i32)>) -> Vec<Band> {
+        cutpoints.sort_by_key(|(_, cutpoint)| *cutpoint);
+        let count = cutpoints.len();
+        cutpoints
+            .into_iter()
+            .enumerate()
+            .map(|(i, (id, cutpoint))| {
+                let words = id.replace('_', " ");
+                let mut chars = words.chars();
+                let label = chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default();
+                Band {
+                    metric: format!("total_{}", id),
+                    metric_name: format!("Total {}", words),
+                    id,
+                    label,
+                    cutpoint,
+                    color: color(i, count),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Color `index` of `count` bands along the band colors.
+fn color(index: usize, count: usize) -> String {
+    let position = match count {
+        1 => 0.,
+        _ => index as f64 / (count - 1) as f64 * (BAND_COLORS.len() - 1) as f64,
+    };
+    let lower = (position.floor() as usize).min(BAND_COLORS.len() - 2);
+    let share = position - lower as f64;
+    let (from, to) = (BAND_COLORS[lower], BAND_COLORS[lower + 1]);
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * share).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+/// Index of the band of a worn epoch, as in [`Intensity::classify`].
+pub fn band_index(value: i32, bands: &[Band]) -> Option<usize> {
+    if value < 0 || bands.is_empty() {
+        return None;
+    }
+    Some(bands.iter().rposition(|b| value >= b.cutpoint).unwrap_or(0))
+}
+
+/// Intensity class of a single epoch, from the configured bands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Intensity<'a> {
+    NonWear,
+    Band(&'a Band),
+}
+
+impl<'a> Intensity<'a> {
+    /// The highest band the value reaches, the lowest band below every cutpoint.
+    pub fn classify(value: i32, bands: &'a [Band]) -> Self {
+        match band_index(value, bands) {
+            Some(index) => Intensity::Band(&bands[index]),
+            None => Intensity::NonWear,
+        }
+    }
+
+    /// Identifier used in the exports and translation keys.
+    pub fn id(&self) -> &'a str {
+        match self {
+            Intensity::NonWear => "non_wear",
+            Intensity::Band(band) => &band.id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bands_keep_their_colors() {
+        let colors: Vec<String> = Band::defaults(50, 500, 1000).into_iter().map(|b| b.color).collect();
+
+        assert_eq!(colors, ["#d9d9d9", "#fdcc8a", "#fc8d59", "#d7301f"]);
+    }
+
+    #[test]
+    fn named_bands_are_ordered_by_cutpoint() {
+        let bands = Band::named(vec![
+            ("very_vigorous".to_string(), 2000),
+            ("rest".to_string(), 0),
+            ("light".to_string(), 50),
+        ]);
+
+        assert_eq!(bands.iter().map(|b| b.id.as_str()).collect::<Vec<_>>(), ["rest", "light", "very_vigorous"]);
+        assert_eq!(bands[2].metric, "total_very_vigorous");
+        assert_eq!(bands[2].label, "Very vigorous");
+        assert_eq!(Intensity::classify(49, &bands), Intensity::Band(&bands[0]));
+        assert_eq!(Intensity::classify(5000, &bands).id(), "very_vigorous");
+        assert_eq!(Intensity::classify(-1, &bands), Intensity::NonWear);
+    }
+}
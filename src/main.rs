@@ -1,9 +1,52 @@
+mod aggregate;
+mod bouts;
+mod charts;
+mod circadian;
+mod cohort;
+mod comparison;
+mod columns;
+mod config;
+mod continuity;
+mod cosinor;
+mod diary;
+mod distribution;
+mod epochs;
+mod exclusions;
+mod export;
+mod html;
+mod i18n;
+mod intensity;
+mod metadata;
+mod metrics;
+mod plots;
+mod prepost;
+mod report;
+mod stats;
+#[cfg(test)]
+mod testing;
+mod weeks;
+mod window;
+
 use std::{collections::HashMap, error::Error};
 
+use aggregate::{PeriodKind, Summary};
+use bouts::Bout;
 use calamine::{open_workbook, Xlsx, Reader};
-use chrono::{NaiveDate, NaiveTime, Duration, Datelike, Weekday};
-use configparser::ini::Ini;
-use rust_xlsxwriter::{Workbook, Format, ExcelDateTime, Color, FormatBorder, Chart};
+use circadian::Rhythm;
+use chrono::{NaiveDate, NaiveTime, NaiveDateTime, Duration, Datelike, Timelike};
+use columns::ColumnRegistry;
+use config::{Config, WindowSpec};
+use continuity::{IssueKind, QualityIssue};
+use cosinor::Fit;
+use diary::EventSummary;
+use epochs::EpochRow;
+use exclusions::Exclusion;
+use i18n::Translations;
+use intensity::Intensity;
+use metadata::DogMetadata;
+use metrics::{builtin_metrics, MetricRegistry, NumberFormat};
+use window::AnalysisWindow;
+use rust_xlsxwriter::{Workbook, Format, Formula, ExcelDateTime, Color, FormatBorder, Chart, ChartType};
 
 #[derive(Debug, PartialEq)]
 enum Mode {
@@ -11,7 +54,8 @@ enum Mode {
     Waiting,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
 struct SensorEntry {
     date: NaiveDate,
     time: NaiveTime,
@@ -22,48 +66,22 @@ struct SensorEntry {
     sedentary: bool,
     con_vig: bool,
     con_mod: bool,
+    exclusion: Option<String>,
+    event: Option<String>,
 }
 
 impl SensorEntry {
     fn from(data: &[calamine::DataType]) -> Option<Self> {
-        let date = match extract_date(&data[0]) {
-            Some(d) => d,
-            None => return None,
-        };
-        let time = match extract_time(&data[1]) {
-            Some(d) => d,
-            None => return None,
-        };
-
-        let value = match extract_mag_value(&data[2]) {
-            Some(d) => d,
-            None => return None,
-        };
-
-        let mut vigorus = match extract_Y_N(&data[3]) {
-            Some(d) => d,
-            None => return None,
-        };
-        let mut moderate = match extract_Y_N(&data[4]) {
-            Some(d) => d,
-            None => return None,
-        };
-        let mut low = match extract_Y_N(&data[5]) {
-            Some(d) => d,
-            None => return None,
-        };
-        let mut sedentary = match extract_Y_N(&data[6]) {
-            Some(d) => d,
-            None => return None,
-        };
-        let mut con_vig = match extract_Y_N(&data[7]) {
-            Some(d) => d,
-            None => return None,
-        };
-        let con_mod = match extract_Y_N(&data[8]) {
-            Some(d) => d,
-            None => return None,
-        };
+        let date = extract_date(&data[0])?;
+        let time = extract_time(&data[1])?;
+        let value = extract_mag_value(&data[2])?;
+
+        let vigorus = extract_y_n(&data[3])?;
+        let moderate = extract_y_n(&data[4])?;
+        let low = extract_y_n(&data[5])?;
+        let sedentary = extract_y_n(&data[6])?;
+        let con_vig = extract_y_n(&data[7])?;
+        let con_mod = extract_y_n(&data[8])?;
         Some(Self {
             date,
             time,
@@ -74,348 +92,180 @@ impl SensorEntry {
             sedentary,
             con_vig,
             con_mod,
+            exclusion: None,
+            event: None,
         })
     }
-}
-
-fn main() {
-    
-    let config = match Ini::new().load("config.ini") {
-        Ok(f) => f,
-        Err(e) => {
-            println!("Error: Can't find config.ini! Make sure it's in the same folder.\nSource: {:#?}", e.to_string());
-            return;
-        },
-    };
-
-    let general_config = match config.get("general") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find [general] section config.ini");
-            return;
-        },
-    };
-
-    let parsing_config = match config.get("parsing") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find [general] section config.ini");
-            return;
-        },
-    };
-
-    let format_config = match config.get("format") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find [general] section config.ini");
-            return;
-        },
-    };
 
-    // general - input_file
-    let input_file_ref = match general_config.get("input_file") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"input_file\" attribute in the [general] section of config.ini");
-            return;
-        },
-    };
-
-    let input_file = match input_file_ref {
-        Some(f) => f.clone(),
-        None => {
-            println!("Error: Can't find \"input_file\" attribute in the [general] section of config.ini");
-            return;
-        },
-    };
-
-    // general - input_file_sheet
-    let input_file_sheet_ref = match general_config.get("input_file_sheet") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"input_file_sheet\" attribute in the [general] section of config.ini");
-            return;
-        },
-    };
-
-    let input_file_sheet = match input_file_sheet_ref {
-        Some(f) => f.clone(),
-        None => {
-            println!("Error: Can't find \"input_file_sheet\" attribute in the [general] section of config.ini");
-            return;
-        },
-    };
+    /// Placeholder for an epoch missing from the export, counted as empty and
+    /// left out of the other metrics.
+    fn empty(timestamp: NaiveDateTime) -> Self {
+        Self {
+            date: timestamp.date(),
+            time: timestamp.time(),
+            value: -1,
+            vigorus: false,
+            moderate: false,
+            low: false,
+            sedentary: false,
+            con_vig: false,
+            con_mod: false,
+            exclusion: None,
+            event: None,
+        }
+    }
 
-    // general - output_file
-    let output_file_ref = match general_config.get("output_file") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"output_file\" attribute in the [general] section of config.ini");
-            return;
-        },
-    };
+    fn timestamp(&self) -> NaiveDateTime {
+        self.date.and_time(self.time)
+    }
+}
 
-    let output_file = match output_file_ref {
-        Some(f) => f.clone(),
-        None => {
-            println!("Error: Can't find \"output_file\" attribute in the [general] section of config.ini");
-            return;
-        },
-    };
+/// The recording after cleaning, windowing, exclusions and diary events,
+/// everything the reports are written from.
+struct Analysis {
+    metadata: Option<DogMetadata>,
+    window: AnalysisWindow,
+    exclusions: Vec<Exclusion>,
+    sensor_data: HashMap<NaiveDate, Vec<SensorEntry>>,
+    excluded_data: HashMap<NaiveDate, Vec<SensorEntry>>,
+    quality_issues: Vec<QualityIssue>,
+    bouts: Vec<Bout>,
+    event_summaries: Option<Vec<EventSummary>>,
+}
 
-    // format - output_number_decimals 
-    let decimals_format_ref = match format_config.get("decimals") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"decimals\" attribute in the [format] section of config.ini");
-            return;
-        },
-    };
+/// A named `[windows]` part of the analysed recording.
+struct WindowAnalysis {
+    name: String,
+    summary: Summary,
+    rhythm: Rhythm,
+    fits: Vec<Fit>,
+}
 
-    let decimals_format = match decimals_format_ref {
-        Some(f) => f.clone(),
-        None => {
-            println!("Error: Can't find \"decimals\" attribute in the [format] section of config.ini");
-            return;
-        },
-    };
+fn main() {
     
-    // format - date_format 
-    let date_format_ref = match format_config.get("date") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"date\" attribute in the [format] section of config.ini");
-            return;
-        },
-    };
-
-    let date_format = match date_format_ref {
-        Some(f) => f.clone(),
-        None => {
-            println!("Error: Can't find \"date\" attribute in the [format] section of config.ini");
-            return;
-        },
-    };
-
-    // format - time_format 
-    let time_format_ref = match format_config.get("time") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"time\" attribute in the [format] section of config.ini");
-            return;
-        },
-    };
-
-    let time_format = match time_format_ref {
-        Some(f) => f.clone(),
-        None => {
-            println!("Error: Can't find \"time\" attribute in the [format] section of config.ini");
-            return;
-        },
-    };
-
-    // format - weekend_color 
-    let weekend_color_ref = match format_config.get("weekend_color") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"weekend_color\" attribute in the [format] section of config.ini");
+    let mut config = match Config::load("config.ini") {
+        Ok(c) => c,
+        Err(e) => {
+            println!("Error: {}", e);
             return;
         },
     };
 
-    let weekend_color: u32 = match weekend_color_ref {
-        Some(f) => match u32::from_str_radix(f, 16) {
-            Ok(v) => v,
-            Err(_) => {
-                println!("Error: Can't parse \"weekend_color\" attribute in the [parsing] section of config.ini. Must be an hex RGB.");
-                return;
-            },
-        }
-        None => {
-            println!("Error: Can't find \"weekend_color\" attribute in the [format] section of config.ini");
+    let metric_registry = match MetricRegistry::from_config(&config) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("Error: {}", e);
             return;
         },
     };
 
-    // format - week_color 
-    let week_color_ref = match format_config.get("week_color") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"week_color\" attribute in the [format] section of config.ini");
+    let translations = match Translations::from_config(&config) {
+        Ok(t) => t,
+        Err(e) => {
+            println!("Error: {}", e);
             return;
         },
     };
 
-    let week_color: u32 = match week_color_ref {
-        Some(f) => match u32::from_str_radix(f, 16) {
-            Ok(v) => v,
-            Err(_) => {
-                println!("Error: Can't parse \"week_color\" attribute in the [parsing] section of config.ini. Must be an hex RGB.");
-                return;
-            },
-        }
-        None => {
-            println!("Error: Can't find \"week_color\" attribute in the [format] section of config.ini");
+    let column_registry = match ColumnRegistry::from_config(&metric_registry, &translations, &config) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("Error: {}", e);
             return;
         },
     };
 
-    // parsing - skip_days_num
-    let skip_days_num_ref = match parsing_config.get("skip_days_num") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"skip_days_num\" attribute in the [parsing] section of config.ini");
-            return;
-        },
-    };
+    if let Some(path) = &config.cohort_output {
+        match write_cohort(path, &metric_registry, &translations, &config) {
+            Ok(_) => println!("Done!"),
+            Err(e) => println!("Error: {}", e),
+        };
+        return;
+    }
 
-    let skip_days_num: i32 = match skip_days_num_ref {
-        Some(f) => match f.clone().parse() {
-            Ok(v) => v,
+    let mut dog_metadata = None;
+    if let Some(path) = &config.metadata_file {
+        dog_metadata = match metadata::load(path, &config.dog) {
+            Ok(m) => m,
             Err(e) => {
-                println!("Error: Can't parse \"skip_days_num\" attribute in the [parsing] section of config.ini. Must be an integer");
+                println!("Error: {}", e);
                 return;
             },
-        },
-        None => {
-            println!("Error: Can't find \"skip_days_num\" attribute in the [parsing] section of config.ini");
-            return;
-        },
-    };
+        };
+        if dog_metadata.is_none() {
+            println!("Warning: dog \"{}\" not found in metadata file {}", config.dog, path);
+        }
+    }
 
-    // parsing - day_window_size
-    let day_window_size_ref = match parsing_config.get("day_window_size") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"day_window_size\" attribute in the [parsing] section of config.ini");
-            return;
-        },
-    };
+    if let Some(path) = &config.prepost_output {
+        match prepost::write_workbook(path, dog_metadata, &metric_registry, &column_registry, &translations, &config) {
+            Ok(_) => println!("Done!"),
+            Err(e) => println!("Error: {}", e),
+        };
+        return;
+    }
 
-    let day_window_size: i32 = match day_window_size_ref {
-        Some(f) => match f.clone().parse() {
-            Ok(v) => v,
-            Err(_) => {
-                println!("Error: Can't parse \"day_window_size\" attribute in the [parsing] section of config.ini. Must be an integer");
-                return;
-            },
-        },
-        None => {
-            println!("Error: Can't find \"day_window_size\" attribute in the [parsing] section of config.ini");
+    let analysis = match analyse(&mut config, dog_metadata, &translations) {
+        Ok(a) => a,
+        Err(e) => {
+            println!("Error: {}", e);
             return;
         },
     };
 
-    // parsing - epoch_seconds
-    let epoch_seconds_ref = match parsing_config.get("epoch_seconds") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"epoch_seconds\" attribute in the [parsing] section of config.ini");
-            return;
-        },
-    };
+    let summary = Summary::compute(&analysis, &metric_registry, &config);
 
-    let epoch_seconds: i32 = match epoch_seconds_ref {
-        Some(f) => match f.clone().parse() {
-            Ok(v) => v,
-            Err(_) => {
-                println!("Error: Can't parse \"epoch_seconds\" attribute in the [parsing] section of config.ini. Must be an integer");
-                return;
-            },
-        },
-        None => {
-            println!("Error: Can't find \"epoch_seconds\" attribute in the [parsing] section of config.ini");
-            return;
-        },
-    };
+    if let Err(e) = write_exports(&analysis, &summary, &metric_registry, &translations, &config) {
+        println!("Error: {}", e);
+        return;
+    }
 
-    // parsing - cutpoint_low
-    let cutpoint_low_ref = match parsing_config.get("cutpoint_low") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"cutpoint_low\" attribute in the [parsing] section of config.ini");
-            return;
-        },
-    };
+    // named windows are parts of the analysed days, counted like the Daily rows
+    let mut windows = vec![];
+    if let Some(first_day) = sorted_dates(&analysis.sensor_data).first() {
+        let mut specs: Vec<(AnalysisWindow, &WindowSpec)> = config.windows.iter().map(|s| (AnalysisWindow::of(s, *first_day), s)).collect();
+        specs.sort_by_key(|(window, _)| window.start);
+        for (window, spec) in specs.into_iter() {
+            let window = slice(&analysis, window);
+            windows.push(WindowAnalysis {
+                name: spec.name.clone(),
+                summary: Summary::compute(&window, &metric_registry, &config),
+                rhythm: Rhythm::compute(&window, &config),
+                fits: cosinor::fits(&window, &config),
+            });
+        }
+    }
 
-    let cutpoint_low: i32 = match cutpoint_low_ref {
-        Some(f) => match f.clone().parse() {
-            Ok(v) => v,
-            Err(_) => {
-                println!("Error: Can't parse \"cutpoint_low\" attribute in the [parsing] section of config.ini. Must be an integer");
-                return;
-            },
-        },
-        None => {
-            println!("Error: Can't find \"cutpoint_low\" attribute in the [parsing] section of config.ini");
-            return;
-        },
+    match summarize(&analysis, &summary, &windows, &metric_registry, &column_registry, &translations, &config) {
+        Ok(_) => println!("Done!"),
+        Err(e) => println!("Error: {:#?}", e.to_string()),
     };
 
-    // parsing - cutpoint_moderate
-    let cutpoint_moderate_ref = match parsing_config.get("cutpoint_moderate") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"cutpoint_moderate\" attribute in the [parsing] section of config.ini");
-            return;
-        },
-    };
+}
 
-    let cutpoint_moderate: i32 = match cutpoint_moderate_ref {
-        Some(f) => match f.clone().parse() {
-            Ok(v) => v,
-            Err(_) => {
-                println!("Error: Can't parse \"cutpoint_moderate\" attribute in the [parsing] section of config.ini. Must be an integer");
-                return;
-            },
-        },
-        None => {
-            println!("Error: Can't find \"cutpoint_moderate\" attribute in the [parsing] section of config.ini");
-            return;
-        },
-    };
 
-    // parsing - cutpoint_vigorus
-    let cutpoint_vigorus_ref = match parsing_config.get("cutpoint_vigorus") {
-        Some(f) => f,
-        None => {
-            println!("Error: Can't find \"cutpoint_vigorus\" attribute in the [parsing] section of config.ini");
-            return;
-        },
-    };
 
-    let cutpoint_vigorus: i32 = match cutpoint_vigorus_ref {
-        Some(f) => match f.clone().parse() {
-            Ok(v) => v,
-            Err(_) => {
-                println!("Error: Can't parse \"cutpoint_vigorus\" attribute in the [parsing] section of config.ini. Must be an integer");
-                return;
-            },
-        },
-        None => {
-            println!("Error: Can't find \"epoch_seconds\" attribute in the [parsing] section of config.ini");
-            return;
-        },
+/// Reads the input workbook of `config` and runs every step up to the values
+/// the reports are written from.
+fn analyse(config: &mut Config, dog_metadata: Option<DogMetadata>, translations: &Translations) -> Result<Analysis, String> {
+    let mut workbook: Xlsx<_> = match open_workbook(&config.input_file) {
+        Ok(w) => w,
+        Err(e) => return Err(format!("Can't open input file {}.\nSource: {}", config.input_file, e)),
     };
-
-    
-    let mut workbook: Xlsx<_> = open_workbook(input_file).expect("Cannot open input *.xlsx file");
     
     let mut state = Mode::Waiting;
-    let mut first_seen_date = None;
-    let mut first_parsed_date = None;
-    let mut sensor_data: HashMap<NaiveDate, Vec<SensorEntry>> = HashMap::new();
+    let mut entries = vec![];
 
-    if let Some(Ok(r)) = workbook.worksheet_range(&input_file_sheet) {
+    if let Some(Ok(r)) = workbook.worksheet_range(&config.input_file_sheet) {
         for row in r.rows() {
 
-            if is_empty(&row) {
+            if is_empty(row) {
                 state = Mode::Waiting;
                 continue;
             }
 
             
-            if state == Mode::Waiting && is_header_row(&row) {
+            if state == Mode::Waiting && is_header_row(row) {
                 state = Mode::Parsing;
                 continue;
             }
@@ -424,71 +274,99 @@ fn main() {
                 continue;
             }
 
-            let sensor_entry = if state == Mode::Parsing {
-                match SensorEntry::from(row) {
-                    Some(v) => v,
-                    None => {
-                        state = Mode::Waiting;
-                        continue;
-                    },
-                }
-            } else {
-                continue;
-            };
-
-            if first_seen_date.is_none() {
-                first_seen_date = Some(sensor_entry.date.clone())
+            match SensorEntry::from(row) {
+                Some(v) => entries.push(v),
+                None => state = Mode::Waiting,
             }
+        }
+    }
 
+    let epoch_issue = continuity::check_epoch(&entries, config)?;
 
-            let first_seen_date_value = first_seen_date.unwrap();
-
-            // check if still skipping first X days
-            if sensor_entry.date.signed_duration_since(first_seen_date_value).num_days() < skip_days_num.into() {
-                continue;
-            }
-
-            if first_parsed_date.is_none() {
-                first_parsed_date = Some(sensor_entry.date.clone())
-            }
-            let first_parsed_date_value = first_parsed_date.unwrap();
+    let (entries, mut quality_issues) = continuity::check(entries, config);
+    if let Some(issue) = epoch_issue {
+        quality_issues.insert(0, issue);
+    }
 
+    let mut analysis_window = AnalysisWindow {
+        start: config.start_datetime,
+        end: config.end_datetime,
+    };
+    if let Some(m) = &dog_metadata {
+        analysis_window.start = m.get_datetime("start")?.or(analysis_window.start);
+        analysis_window.end = m.get_datetime("end")?.or(analysis_window.end);
+    }
 
-            // check if all required dates parsed
-            if sensor_entry.date.signed_duration_since(first_parsed_date_value).num_days() >= day_window_size.into() {
-                break;
-            }
+    let mut sensor_data = window::apply(entries, &analysis_window, config);
 
-            
-            let entry = sensor_data.entry(sensor_entry.date).or_insert(vec![]);
-            entry.push(sensor_entry);
+    let diary_events = match &config.diary_file {
+        Some(path) => diary::load_file(path, &config.dog)?,
+        None => vec![],
+    };
+    quality_issues.extend(diary::apply(&mut sensor_data, &diary_events, config));
 
-            
+    let mut exclusion_list = config.exclusions.clone();
+    if let Some(path) = &config.exclusions_file {
+        exclusion_list.extend(exclusions::load_file(path, &config.dog)?);
+    }
+    let (excluded_data, exclusion_issues) = exclusions::apply(&mut sensor_data, &exclusion_list);
+    quality_issues.extend(exclusion_issues);
+    quality_issues.sort_by_key(|i| i.timestamp);
+    for kind in [IssueKind::EpochMismatch, IssueKind::Gap, IssueKind::Duplicate, IssueKind::OutOfOrder, IssueKind::OpenEvent] {
+        let count = quality_issues.iter().filter(|i| i.kind == kind).count();
+        if count > 0 {
+            println!("Warning: {} {} issue(s) found, see the \"{}\" sheet", count, kind.label(), translations.get("sheet.quality"));
         }
     }
-    
-    println!("{:#?}", sensor_data.keys());
 
-    match summarize(
-        sensor_data,
-        output_file, 
-        &date_format, 
-        &time_format,
-        &decimals_format, 
-        epoch_seconds,
-        cutpoint_low,
-        cutpoint_moderate,
-        cutpoint_vigorus,
-        weekend_color,
-        week_color,
-    ) {
-        Ok(_) => println!("Done!"),
-        Err(e) => println!("Error: {:#?}", e.to_string()),
-    };
+    let event_summaries = config.diary_file
+        .as_ref()
+        .map(|_| diary::summarize(&sensor_data, &diary_events, config));
+
+    let bouts = bouts::detect(&sensor_data, config);
 
+    Ok(Analysis {
+        metadata: dog_metadata,
+        window: analysis_window,
+        exclusions: exclusion_list,
+        sensor_data,
+        excluded_data,
+        quality_issues,
+        bouts,
+        event_summaries,
+    })
 }
 
+/// The part of `analysis` inside `window`, e.g. a named window. Days excluded
+/// entirely are kept like in the whole analysis.
+fn slice(analysis: &Analysis, window: AnalysisWindow) -> Analysis {
+    let inside = |data: &HashMap<NaiveDate, Vec<SensorEntry>>, date: &NaiveDate| -> Vec<SensorEntry> {
+        data.get(date).into_iter().flatten().filter(|e| window.contains(e.timestamp())).cloned().collect()
+    };
+    let mut sensor_data = HashMap::new();
+    let mut excluded_data = HashMap::new();
+    for date in analysis.sensor_data.keys() {
+        let (entries, excluded) = (inside(&analysis.sensor_data, date), inside(&analysis.excluded_data, date));
+        if entries.is_empty() && excluded.is_empty() {
+            continue;
+        }
+        if !excluded.is_empty() {
+            excluded_data.insert(*date, excluded);
+        }
+        sensor_data.insert(*date, entries);
+    }
 
+    Analysis {
+        metadata: analysis.metadata.clone(),
+        window,
+        exclusions: analysis.exclusions.clone(),
+        sensor_data,
+        excluded_data,
+        quality_issues: analysis.quality_issues.iter().filter(|i| window.contains(i.timestamp)).cloned().collect(),
+        bouts: analysis.bouts.iter().filter(|b| window.contains(b.start)).cloned().collect(),
+        event_summaries: None,
+    }
+}
 
 fn is_header_row(row: &[calamine::DataType]) -> bool {
     match &row[0] {
@@ -527,7 +405,7 @@ fn extract_date(cell: &calamine::DataType) -> Option<NaiveDate> {
             // Excel's datetime is a float where the integer part is the number of days since 1900-01-01
             // and the decimal part represents the time of the day.
             let days = float.trunc() as i64;
-            let naive_date = NaiveDate::from_ymd(1900, 1, 1) + Duration::days(days - 2);
+            let naive_date = NaiveDate::from_ymd_opt(1900, 1, 1)? + Duration::days(days - 2);
             Some(naive_date)
         },
         _ => None,
@@ -540,12 +418,10 @@ fn extract_time(cell: &calamine::DataType) -> Option<NaiveTime> {
         calamine::DataType::DateTime(float) => {
             // Excel's datetime is a float where the integer part is the number of days since 1900-01-01
             // and the decimal part represents the time of the day.
+            // Rounded to the nearest second, truncating turns e.g. 10:00:15 into 10:00:14.
             let days_proportion = float.fract();
-            let days = float.trunc() as i64;
-
-            let naive_time = NaiveTime::from_num_seconds_from_midnight(
-                (days_proportion * 24.0 * 60.0 * 60.0) as u32, 0);
-            Some(naive_time)
+            let seconds = (days_proportion * 24.0 * 60.0 * 60.0).round() as u32 % 86400;
+            NaiveTime::from_num_seconds_from_midnight_opt(seconds, 0)
         },
         _ => None,
     }
@@ -560,7 +436,7 @@ fn extract_mag_value(cell: &calamine::DataType) -> Option<i32> {
 }
 
 
-fn extract_Y_N(cell: &calamine::DataType) -> Option<bool> {
+fn extract_y_n(cell: &calamine::DataType) -> Option<bool> {
     match cell {
         calamine::DataType::String(s) => {
             if s.eq("Y") || s.eq("N") {
@@ -579,509 +455,441 @@ fn sorted_dates<T>(map: &HashMap<NaiveDate, T>) -> Vec<NaiveDate> {
     dates
 }
 
+/// Cohort mode, every dog of the metadata file in one workbook.
+fn write_cohort(path: &str, metrics: &MetricRegistry, translations: &Translations, config: &Config) -> Result<(), Box<dyn Error>> {
+    let dogs = cohort::collect(config, metrics, translations)?;
+    let mut workbook = cohort::write_workbook(&dogs, metrics, translations, config)?;
+    comparison::write_statistics_sheet(&mut workbook, &dogs, metrics, translations, config)?;
+    let rhythms: Vec<(String, &Rhythm)> = dogs.iter().map(|d| (d.metadata.dog.clone(), &d.rhythm)).collect();
+    circadian::write_sheet(&mut workbook, &rhythms, translations, config)?;
+    let fits: Vec<(String, &[Fit])> = dogs.iter().map(|d| (d.metadata.dog.clone(), &d.fits[..])).collect();
+    cosinor::write_sheet(&mut workbook, &fits, translations, config)?;
+    workbook.save(path)?;
+    Ok(())
+}
+
+fn write_exports(
+    analysis: &Analysis,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    translations: &Translations,
+    config: &Config,
+) -> Result<(), String> {
+    if let Some(path) = &config.epochs_csv {
+        epochs::write_csv(path, &epochs::annotate(analysis, config))?;
+    }
+    if let Some(path) = &config.json_report {
+        report::write_json(path, analysis, summary, metrics, config)?;
+    }
+    if let Some(path) = &config.html_report {
+        html::write_report(path, analysis, summary, metrics, translations, config)?;
+    }
+    plots::write_files(analysis, summary, metrics, translations, config)?;
+    if config.tidy_csv.is_none() && config.tidy_parquet.is_none() {
+        return Ok(());
+    }
+    let rows = export::tidy_rows(&config.dog, summary, metrics);
+    if let Some(path) = &config.tidy_csv {
+        export::write_tidy_csv(path, &rows)?;
+    }
+    if let Some(path) = &config.tidy_parquet {
+        #[cfg(feature = "parquet")]
+        export::write_tidy_parquet(path, &rows)?;
+        #[cfg(not(feature = "parquet"))]
+        return Err(format!("Can't write {}, this build has no Parquet support. Rebuild with `cargo build --release --features parquet`", path));
+    }
+    Ok(())
+}
+
+/// Cell formats of the Daily sheet. The Daily rows recolor them for weekends
+/// and weeks, the sheets written afterwards keep the last colors.
+struct SheetFormats {
+    basic: Format,
+    bold: Format,
+    decimal: Format,
+    date: Format,
+    time: Format,
+}
+
+impl SheetFormats {
+    fn new(config: &Config) -> Self {
+        Self {
+            basic: Format::new(),
+            bold: Format::new().set_bold().set_border(FormatBorder::Hair),
+            decimal: Format::new().set_num_format(&config.decimals_format),
+            date: Format::new().set_num_format(&config.date_format),
+            time: Format::new().set_num_format(&config.time_format),
+        }
+    }
+}
+
 fn summarize(
-    sensor_data: HashMap<NaiveDate, Vec<SensorEntry>>, 
-    out_file: String, 
-    date_format: &str, 
-    time_format: &str, 
-    decimal_format: &str, 
-    epoch_time: i32,
-    cutpoint_low: i32,
-    cutpoint_moderate: i32,
-    cutpoint_vigorus: i32,
-    weekend_color: u32,
-    week_color: u32,
+    analysis: &Analysis,
+    summary: &Summary,
+    windows: &[WindowAnalysis],
+    metrics: &MetricRegistry,
+    registry: &ColumnRegistry,
+    translations: &Translations,
+    config: &Config,
 ) -> Result<(), Box<dyn Error>> {
     let mut workbook = Workbook::new();
-    let sheet =  workbook.add_worksheet();
-    sheet.set_name("Daily")?;
+    let mut formats = SheetFormats::new(config);
+    write_daily_sheet(&mut workbook, summary, metrics, registry, translations, config, &mut formats)?;
+    write_weekly_sheet(&mut workbook, summary, metrics, registry, translations, config, &formats)?;
+
+    for window in windows.iter() {
+        let window_registry = registry.for_window(&window.name);
+        let mut window_formats = SheetFormats::new(config);
+        write_daily_sheet(&mut workbook, &window.summary, metrics, &window_registry, translations, config, &mut window_formats)?;
+        write_weekly_sheet(&mut workbook, &window.summary, metrics, &window_registry, translations, config, &window_formats)?;
+    }
+    if windows.len() > 1 {
+        prepost::write_windows_sheet(&mut workbook, windows, metrics, registry, translations, config)?;
+    }
 
-    let mut basic_format = Format::new();
-    let bold_format = Format::new().set_bold().set_border(FormatBorder::Hair);
-    let mut decimal_format = Format::new().set_num_format(decimal_format);
-    let mut date_format = Format::new().set_num_format(date_format);
-    let mut time_format = Format::new().set_num_format(time_format);
-    let mut weekend_format = Format::new().set_font_color(Color::RGB(weekend_color));
+    let rhythm = Rhythm::compute(analysis, config);
+    let mut rhythms = vec![(translations.get("label.all_days").to_string(), &rhythm)];
+    rhythms.extend(windows.iter().map(|w| (w.name.clone(), &w.rhythm)));
+    circadian::write_sheet(&mut workbook, &rhythms, translations, config)?;
 
+    let fits = cosinor::fits(analysis, config);
+    let mut labelled_fits = vec![(translations.get("label.all_days").to_string(), &fits[..])];
+    labelled_fits.extend(windows.iter().map(|w| (w.name.clone(), &w.fits[..])));
+    cosinor::write_sheet(&mut workbook, &labelled_fits, translations, config)?;
+    distribution::write_sheet(&mut workbook, analysis, translations, config)?;
 
+    charts::write_charts_sheet(&mut workbook, analysis, summary, metrics, registry, translations, &formats.bold)?;
 
-    
+    write_quality_sheet(&mut workbook, &analysis.quality_issues, translations, &formats.bold, &formats.date, &formats.time)?;
 
-    let columns = vec![
-        "Day",
-        "Date",
-        "Weekday",
-        "Total Vig.",
-        "Total Mod.",
-        "Total Low",
-        "Total Sed.",
-        // "Con. Vig.",
-        // "Con. Mod.",
-        "T. Non-zero",
-        "T. Zero",
-        "T. Empty",
-        "Tot Counts",
-        "Ave Counts/Min",
-        "Ave Counts/Epoch",
-    ];
+    if let Some(event_summaries) = &analysis.event_summaries {
+        // event totals can add up to more than a day
+        let duration_format = Format::new().set_num_format("[h]:mm:ss");
+        write_events_sheet(&mut workbook, event_summaries, config, translations, &formats.bold, &duration_format, &formats.decimal)?;
+    }
 
-    for i in 0..columns.len() {
-        sheet.set_column_width(i as u16, 10)?;
-        sheet.write_with_format(0, i as u16, columns[i], &bold_format)?;
+    if config.epochs_sheet {
+        let rows = epochs::annotate(analysis, config);
+        write_epochs_sheet(&mut workbook, &rows, translations, &formats.bold, &formats.date, &formats.time)?;
     }
-    let mut week_counter = 0;
-    let mut current_week_color = Color::White;
-    for (index, day) in sorted_dates(&sensor_data).into_iter().enumerate() {
-        let row = (index + 1) as u32;
 
-        if day.weekday() == Weekday::Sat || day.weekday() == Weekday::Sun {
-            basic_format = basic_format.set_font_color(weekend_color).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-            decimal_format = decimal_format.set_font_color(weekend_color).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-            date_format = date_format.set_font_color(weekend_color).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-            time_format = time_format.set_font_color(weekend_color).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-            weekend_format = weekend_format.set_font_color(weekend_color).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-        } else {
-            basic_format = basic_format.set_font_color(Color::Black).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-            decimal_format = decimal_format.set_font_color(Color::Black).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-            date_format = date_format.set_font_color(Color::Black).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-            time_format = time_format.set_font_color(Color::Black).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-            weekend_format = weekend_format.set_font_color(Color::Black).set_border(FormatBorder::Hair).set_background_color(current_week_color);
-        }
-        
+    workbook.save(&config.output_file)?;
 
-        for col_name in columns.iter() {
-            let position = columns.iter().position(|n| n == col_name).unwrap() as u16;
+    
+    Ok(())
+}
 
-            match *col_name {
-                "Day"               => sheet.write_with_format(row,position, row, &basic_format)?,
-                "Date"              => sheet.write_with_format(row,position, &calc_date(&day)?, &date_format)?,
-                "Weekday"           => sheet.write_with_format(row,position, calc_weekday(&day), &basic_format)?,
-                "Total Vig."        => sheet.write_with_format(row,position, &calc_total_vig(sensor_data.get(&day), epoch_time, cutpoint_vigorus)?, &time_format)?,
-                "Total Mod."        => sheet.write_with_format(row,position, &calc_total_mod(sensor_data.get(&day), epoch_time, cutpoint_moderate, cutpoint_vigorus)?, &time_format)?,
-                "Total Low"         => sheet.write_with_format(row,position, &calc_total_low(sensor_data.get(&day), epoch_time, cutpoint_low, cutpoint_moderate)?, &time_format)?,
-                "Total Sed."        => sheet.write_with_format(row,position, &calc_total_sed(sensor_data.get(&day), epoch_time, cutpoint_low)?, &time_format)?,
-                // "Con. Vig."         => sheet.write(row,position, calc_con_vig(sensor_data.get(&day)))?,
-                // "Con. Mod."         => sheet.write(row,position, calc_con_mod(sensor_data.get(&day)))?,
-                "T. Non-zero"       => sheet.write_with_format(row,position, &calc_t_non_zero(sensor_data.get(&day), epoch_time)?, &time_format)?,
-                "T. Zero"           => sheet.write_with_format(row,position, &calc_t_zero(sensor_data.get(&day), epoch_time)?, &time_format)?,
-                "T. Empty"          => sheet.write_with_format(row,position, &calc_t_empty(sensor_data.get(&day), epoch_time)?, &time_format)?,
-                "Tot Counts"        => sheet.write_with_format(row,position, calc_tot_counts(sensor_data.get(&day)), &basic_format)?,
-                "Ave Counts/Min"    => sheet.write_with_format(row,position, calc_ave_counts_min(sensor_data.get(&day), epoch_time), &decimal_format)?,
-                "Ave Counts/Epoch"  => sheet.write_with_format(row,position, calc_ave_counts_epoch(sensor_data.get(&day), epoch_time), &decimal_format)?,
-                _                   => sheet.write_with_format(row,position, "Not handled!", &basic_format)?,
-            };
-        }
+fn write_daily_sheet(
+    workbook: &mut Workbook,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    registry: &ColumnRegistry,
+    translations: &Translations,
+    config: &Config,
+    formats: &mut SheetFormats,
+) -> Result<(), Box<dyn Error>> {
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(registry.daily_sheet())?;
 
-        week_counter += 1;
-        if week_counter >= 7 {
-            week_counter = 0;
+    let columns = registry.daily_columns();
+
+    for (i, column) in columns.iter().enumerate() {
+        sheet.set_column_width(i as u16, registry.width(column))?;
+        sheet.write_with_format(0, i as u16, registry.header(column), &formats.bold)?;
+    }
+    let mut current_week = None;
+    let mut current_week_color = Color::RGB(config.week_color);
+    for day in summary.days.iter() {
+        // Zero-based Daily row, the header takes row 0
+        let row = day.number as u32;
+
+        let week = weeks::week_start_of(&day.date, config.week_start);
+        if current_week != Some(week) {
+            current_week = Some(week);
             if current_week_color == Color::White {
-                current_week_color = Color::RGB(week_color);
+                current_week_color = Color::RGB(config.week_color);
             } else {
                 current_week_color = Color::White;
             }
         }
-    }
-
-
-    let sheet =  workbook.add_worksheet();
-    sheet.set_name("Weekly")?;
-
-    let columns = vec![
-        "Label",
-        "Total Vig.",
-        "Total Mod.",
-        "Total Low",
-        "Total Sed.",
-        "T. Non-zero",
-        "T. Zero",
-        "T. Empty",
-        "Tot Counts",
-        "Ave Counts/Min",
-        "Ave Counts/Epoch",
-    ];
-
-    for i in 0..columns.len() {
-        sheet.set_column_width(i as u16, 10)?;
-        sheet.write_with_format(0, i as u16, columns[i], &bold_format)?;
-    }
 
-    let mut weekends: Vec<Vec<u32>> = vec![];
-    let mut current_weekend: Vec<u32> = vec![];
-    let mut weekdays: Vec<Vec<u32>> = vec![];
-    let mut current_weekdays: Vec<u32> = vec![];
-
-    for (index, day) in sorted_dates(&sensor_data).into_iter().enumerate() {
-        let row = (index + 2) as u32;
-        if day.weekday() == Weekday::Sat || day.weekday() == Weekday::Sun {
-            current_weekend.push(row);
-            if current_weekend.len() == 2 {
-                weekends.push(current_weekend.clone());
-                current_weekend = vec![];
-            }
-            continue;
+        if weeks::is_weekend(&day.date) {
+            formats.basic = formats.basic.clone().set_font_color(config.weekend_color).set_border(FormatBorder::Hair).set_background_color(current_week_color);
+            formats.decimal = formats.decimal.clone().set_font_color(config.weekend_color).set_border(FormatBorder::Hair).set_background_color(current_week_color);
+            formats.date = formats.date.clone().set_font_color(config.weekend_color).set_border(FormatBorder::Hair).set_background_color(current_week_color);
+            formats.time = formats.time.clone().set_font_color(config.weekend_color).set_border(FormatBorder::Hair).set_background_color(current_week_color);
+        } else {
+            formats.basic = formats.basic.clone().set_font_color(Color::Black).set_border(FormatBorder::Hair).set_background_color(current_week_color);
+            formats.decimal = formats.decimal.clone().set_font_color(Color::Black).set_border(FormatBorder::Hair).set_background_color(current_week_color);
+            formats.date = formats.date.clone().set_font_color(Color::Black).set_border(FormatBorder::Hair).set_background_color(current_week_color);
+            formats.time = formats.time.clone().set_font_color(Color::Black).set_border(FormatBorder::Hair).set_background_color(current_week_color);
         }
 
-        current_weekdays.push(row);
-        if current_weekdays.len() == 5 {
-            weekdays.push(current_weekdays.clone());
-            current_weekdays = vec![];
-        }
-    }
+        for (position, col_name) in columns.iter().enumerate() {
+            let position = position as u16;
 
-    
-    let mut last_row = 0;
-    let mut num_of_weeks = 0;
-    for i in 0..weekdays.len() {
-        last_row += 1;
-        let current_weekdays = match weekdays.get(i) {
-            Some(w) => w,
-            None => continue,
-        };
-        let current_weekend = match weekends.get(i) {
-            Some(w) => w,
-            None => continue,
-        };
-        let mut days = current_weekdays.clone();
-        days.push(current_weekend[0]);
-        days.push(current_weekend[1]);
-        
-        num_of_weeks += 1;
-
-        for col_name in columns.iter() {
-            let position = columns.iter().position(|n| n == col_name).unwrap() as u16;
             match *col_name {
-                "Label"             => sheet.write(last_row, position, format!("Teden {}", i + 1))?,
-                "Total Vig."        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &time_format)?,
-                "Total Mod."        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &time_format)?,
-                "Total Low"         => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &time_format)?,
-                "Total Sed."        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &time_format)?,
-                "T. Non-zero"       => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &time_format)?,
-                "T. Zero"           => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &time_format)?,
-                "T. Empty"          => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &time_format)?,
-                "Tot Counts"        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &decimal_format)?,
-                "Ave Counts/Min"    => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &decimal_format)?,
-                "Ave Counts/Epoch"  => sheet.write_formula_with_format(last_row,position, make_avg_formula(&days, position).as_str(), &decimal_format)?,
-                _                   => sheet.write_with_format(last_row,position, "Not handled!", &basic_format)?,
+                "day"               => sheet.write_with_format(row,position, row, &formats.basic)?,
+                "date"              => sheet.write_with_format(row,position, &calc_date(&day.date)?, &formats.date)?,
+                "weekday"           => sheet.write_with_format(row,position, translations.weekday(day.date.weekday()), &formats.basic)?,
+                id                  => match metrics.position(id) {
+                    Some(index) => match day.values[index] {
+                        // undefined on the day, the cell is left blank
+                        None => &mut *sheet,
+                        Some(value) => match metrics.metrics()[index].number_format() {
+                            NumberFormat::Time      => sheet.write_with_format(row,position, &seconds_to_edt(value.round() as i32)?, &formats.time)?,
+                            NumberFormat::Integer   => sheet.write_with_format(row,position, value, &formats.basic)?,
+                            NumberFormat::Decimal   => sheet.write_with_format(row,position, value, &formats.decimal)?,
+                        },
+                    },
+                    None => sheet.write_with_format(row,position, "Not handled!", &formats.basic)?,
+                },
             };
         }
-
-    }
-
-    last_row += 1;
-    last_row += 1;
-    let columns = vec![
-        "Label",
-        "Total Vig.",
-        "Total Mod.",
-        "Total Low",
-        "Total Sed.",
-        "T. Non-zero",
-        "T. Zero",
-        "T. Empty",
-        "Tot Counts",
-        "Ave Counts/Min",
-        "Ave Counts/Epoch",
-    ];
-
-    for i in 0..columns.len() {
-        sheet.set_column_width(i as u16, 10)?;
-        sheet.write_with_format(last_row, i as u16, columns[i], &bold_format)?;
     }
+    Ok(())
+}
 
-    for i in 0..weekends.len() {
-        last_row += 1;
-        for col_name in columns.iter() {
-            let position = columns.iter().position(|n| n == col_name).unwrap() as u16;
-            let current_weekend = match weekends.get(i) {
-                Some(w) => w,
-                None => continue,
-            };
-            match *col_name {
-                "Label"             => sheet.write(last_row, position, format!("Vikend {}", i + 1))?,
-                "Total Vig."        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &time_format)?,
-                "Total Mod."        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &time_format)?,
-                "Total Low"         => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &time_format)?,
-                "Total Sed."        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &time_format)?,
-                "T. Non-zero"       => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &time_format)?,
-                "T. Zero"           => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &time_format)?,
-                "T. Empty"          => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &time_format)?,
-                "Tot Counts"        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &decimal_format)?,
-                "Ave Counts/Min"    => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &decimal_format)?,
-                "Ave Counts/Epoch"  => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekend.to_vec(), position).as_str(), &decimal_format)?,
-                _                   => sheet.write_with_format(last_row,position, "Not handled!", &basic_format)?,
-            };
-        }
+/// Averages of the weeks, weekends and weekdays below each other, from the
+/// Daily sheet of the same registry.
+fn write_weekly_sheet(
+    workbook: &mut Workbook,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    registry: &ColumnRegistry,
+    translations: &Translations,
+    config: &Config,
+    formats: &SheetFormats,
+) -> Result<(), Box<dyn Error>> {
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(registry.weekly_sheet())?;
 
-    }
+    let columns = registry.weekly_columns();
 
-    last_row += 1;
-    last_row += 1;
-    let columns = vec![
-        "Label",
-        "Total Vig.",
-        "Total Mod.",
-        "Total Low",
-        "Total Sed.",
-        "T. Non-zero",
-        "T. Zero",
-        "T. Empty",
-        "Tot Counts",
-        "Ave Counts/Min",
-        "Ave Counts/Epoch",
+    let sections = [
+        (PeriodKind::Week, translations.get("label.week")),
+        (PeriodKind::Weekend, translations.get("label.weekend")),
+        (PeriodKind::Weekdays, translations.get("label.weekdays")),
     ];
-    
-    for i in 0..columns.len() {
-        sheet.set_column_width(i as u16, 10)?;
-        sheet.write_with_format(last_row, i as u16, columns[i], &bold_format)?;
-    }
-
-    for i in 0..weekdays.len() {
-        last_row += 1;
-        for col_name in columns.iter() {
-            let position = columns.iter().position(|n| n == col_name).unwrap() as u16;
-            let current_weekday = match weekdays.get(i) {
-                Some(w) => w,
-                None => continue,
-            };
-            match *col_name {
-                "Label"             => sheet.write(last_row, position, format!("Dnevi {}", i + 1))?,
-                "Total Vig."        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &time_format)?,
-                "Total Mod."        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &time_format)?,
-                "Total Low"         => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &time_format)?,
-                "Total Sed."        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &time_format)?,
-                "T. Non-zero"       => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &time_format)?,
-                "T. Zero"           => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &time_format)?,
-                "T. Empty"          => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &time_format)?,
-                "Tot Counts"        => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &decimal_format)?,
-                "Ave Counts/Min"    => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &decimal_format)?,
-                "Ave Counts/Epoch"  => sheet.write_formula_with_format(last_row,position, make_avg_formula(&current_weekday.to_vec(), position).as_str(), &decimal_format)?,
-                _                   => sheet.write_with_format(last_row,position, "Not handled!", &basic_format)?,
-            };
+    let mut last_row = 0;
+    for (kind, label) in sections.iter() {
+        if last_row > 0 {
+            last_row += 2;
+        }
+        for (i, column) in columns.iter().enumerate() {
+            sheet.set_column_width(i as u16, registry.width(column))?;
+            sheet.write_with_format(last_row, i as u16, registry.header(column), &formats.bold)?;
         }
 
+        for period in summary.periods.iter().filter(|p| p.kind == *kind) {
+            last_row += 1;
+            let daily_rows: Vec<u32> = period.days.iter().map(|d| d.number as u32).collect();
+
+            for (position, col_name) in columns.iter().enumerate() {
+                let position = position as u16;
+                match *col_name {
+                    "label"             => sheet.write(last_row, position, format!("{} {}", label, period.number))?,
+                    "days"              => sheet.write(last_row, position, period.days.len() as u32)?,
+                    id                  => match (metrics.position(id), registry.average_formula(id, &daily_rows)) {
+                        // undefined on every day of the period, AVERAGE would be #DIV/0!
+                        (Some(index), Some(_)) if period.values[index].is_none() => &mut *sheet,
+                        (Some(index), Some(formula)) => {
+                            let value = period.values[index].unwrap_or_default();
+                            let (format, result) = match metrics.metrics()[index].number_format() {
+                                // Excel keeps times as fractions of a day
+                                NumberFormat::Time => (&formats.time, value.round() / 86400.),
+                                NumberFormat::Integer | NumberFormat::Decimal => (&formats.decimal, value),
+                            };
+                            if config.weekly_formulas {
+                                let formula = Formula::new(formula).set_result(result.to_string());
+                                sheet.write_formula_with_format(last_row, position, formula, format)?
+                            } else {
+                                sheet.write_with_format(last_row, position, result, format)?
+                            }
+                        },
+                        _ => sheet.write_with_format(last_row,position, "Not handled!", &formats.basic)?,
+                    },
+                };
+            }
+        }
     }
 
     last_row += 2;
 
-    let mut chart = Chart::new(rust_xlsxwriter::ChartType::Line);
-    let range = format!("Weekly!I2:I{}", 2 + num_of_weeks);
-    println!("{range}");
-    chart.add_series().set_values(range.as_str());
-    sheet.insert_chart(last_row, 0, &chart)?;
-
-    workbook.save(out_file)?;
-
-    
+    // the week rows come first, right below the header
+    let weeks = summary.periods.iter().filter(|p| p.kind == PeriodKind::Week).count() as u32;
+    if let (Some(label_column), Some(counts_column), true) = (registry.weekly_position("label"), registry.weekly_position("tot_counts"), weeks > 0) {
+        let weekly_sheet = registry.weekly_sheet();
+        let mut chart = Chart::new(ChartType::Line);
+        chart
+            .add_series()
+            .set_name(registry.header("tot_counts"))
+            .set_categories((weekly_sheet, 1, label_column, weeks, label_column))
+            .set_values((weekly_sheet, 1, counts_column, weeks, counts_column));
+        chart.title().set_name(translations.get("chart.weekly_counts"));
+        chart.x_axis().set_name(translations.get("axis.week"));
+        chart.y_axis().set_name(translations.get("axis.counts"));
+        chart.legend().set_hidden();
+        sheet.insert_chart(last_row, 0, &chart)?;
+    }
     Ok(())
 }
 
-fn make_avg_formula(days: &Vec<u32>, position: u16) -> String {
-    let mut out = "".to_string();
-    for d in days.into_iter() {
-        let letter = position_to_letter(position);
-        out = format!("{},Daily!{}{}", out,  letter, d);
-    }
-    out.remove(0);
-    format!("=AVERAGE({})", out)
-}
+fn write_quality_sheet(
+    workbook: &mut Workbook,
+    quality_issues: &[QualityIssue],
+    translations: &Translations,
+    bold_format: &Format,
+    date_format: &Format,
+    time_format: &Format,
+) -> Result<(), Box<dyn Error>> {
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.quality"))?;
+
+    let columns = [
+        "type",
+        "date",
+        "time",
+        "details",
+        "action",
+    ];
 
-fn position_to_letter(position: u16) -> String {
-    match position {
-        1 => "D".to_string(),
-        2 => "E".to_string(),
-        3 => "F".to_string(),
-        4 => "G".to_string(),
-        5 => "H".to_string(),
-        6 => "I".to_string(),
-        7 => "J".to_string(),
-        8 => "K".to_string(),
-        9 => "L".to_string(),
-        10 => "M".to_string(),
-        _  => "N".to_string(),
+    for (i, column) in columns.iter().enumerate() {
+        sheet.set_column_width(i as u16, 12)?;
+        sheet.write_with_format(0, i as u16, translations.column(column, column), bold_format)?;
     }
-}
-
-fn calc_ave_counts_min(
-    day: Option<&Vec<SensorEntry>>,
-    epoch_time: i32,
-) -> f32 {
-    let day = match day {
-        Some(day) => day,
-        None => return -99.,
-    };
-    day.iter().map(|s| s.value).sum::<i32>() as f32 / (day.len() as f32 / (60. / epoch_time as f32) )
-}
-
-fn calc_ave_counts_epoch(
-    day: Option<&Vec<SensorEntry>>,
-    epoch_time: i32,
-) -> f32 {
-    let avg = calc_ave_counts_min(day, epoch_time);
-    avg / (60. / epoch_time as f32)
-}
-
-fn avg_count(day: &Vec<SensorEntry>) -> f32 {
-    day
-        .iter()
-        .map(|s| s.value)
-        .sum::<i32>() as f32 
-            / (day.len() as f32)
-}
-
-fn calc_tot_counts(day: Option<&Vec<SensorEntry>>) -> i32 {
-    let day = match day {
-        Some(day) => day,
-        None => return -99,
-    };
-    day.iter().map(|s| s.value).sum::<i32>()
-}
-
-fn calc_t_empty(
-    day: Option<&Vec<SensorEntry>>,
-    epoch_time: i32,
-) -> Result<ExcelDateTime, Box<dyn Error>>  {
-    let day = match day {
-        Some(day) => day,
-        None => return Err("No data".to_string().into()),
-    };
+    sheet.set_column_width(3, 50)?;
 
-    let mut count = 0;
-    for entry in day.iter() {
-        if entry.value == -1 {
-            count += 1;
-        }
+    if quality_issues.is_empty() {
+        sheet.write(1, 0, translations.get("label.no_issues"))?;
+        return Ok(());
     }
-    count *= epoch_time;
-    seconds_to_edt(count)
-}
 
-fn calc_t_zero(
-    day: Option<&Vec<SensorEntry>>,
-    epoch_time: i32,
-) -> Result<ExcelDateTime, Box<dyn Error>> {
-    let day = match day {
-        Some(day) => day,
-        None => return Err("No data".to_string().into()),
-    };
-
-    let mut count = 0;
-    for entry in day.iter() {
-        if entry.value == 0 {
-            count += 1;
-        }
+    for (index, issue) in quality_issues.iter().enumerate() {
+        let row = (index + 1) as u32;
+        let time = ExcelDateTime::from_hms(
+            issue.timestamp.hour() as u16,
+            issue.timestamp.minute() as u8,
+            issue.timestamp.second() as f64,
+        )?;
+        sheet.write(row, 0, translations.issue(issue.kind))?;
+        sheet.write_with_format(row, 1, &calc_date(&issue.timestamp.date())?, date_format)?;
+        sheet.write_with_format(row, 2, &time, time_format)?;
+        sheet.write(row, 3, &issue.detail)?;
+        sheet.write(row, 4, &issue.action)?;
     }
-    count *= epoch_time;
-    seconds_to_edt(count)
+    Ok(())
 }
 
-fn calc_t_non_zero(
-    day: Option<&Vec<SensorEntry>>,
-    epoch_time: i32,
-) -> Result<ExcelDateTime, Box<dyn Error>> {
-    let day = match day {
-        Some(day) => day,
-        None => return Err("No data".to_string().into()),
-    };
-
-    let mut count = 0;
-    for entry in day.iter() {
-        if entry.value > 0 {
-            count += 1;
-        }
+fn write_events_sheet(
+    workbook: &mut Workbook,
+    event_summaries: &[EventSummary],
+    config: &Config,
+    translations: &Translations,
+    bold_format: &Format,
+    time_format: &Format,
+    decimal_format: &Format,
+) -> Result<(), Box<dyn Error>> {
+    let epoch_time = config.epoch_seconds;
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.events"))?;
+
+    // a time column per band, from the most intense one
+    let mut columns = vec!["event", "occurrences", "duration"];
+    columns.extend(config.bands.iter().rev().map(|b| b.metric.as_str()));
+    columns.extend(["tot_counts", "ave_counts_min", "ave_counts_epoch"]);
+
+    for (i, column) in columns.iter().enumerate() {
+        // metric columns are named like on the Daily sheet, even when disabled there
+        let default = builtin_metrics(&config.bands).into_iter().find(|m| m.id() == *column).map(|m| m.name().to_string());
+        let header = translations.column(column, default.as_deref().unwrap_or(column));
+        sheet.set_column_width(i as u16, 10)?;
+        sheet.write_with_format(0, i as u16, header, bold_format)?;
     }
-    count *= epoch_time;
-    seconds_to_edt(count)
-}
+    sheet.set_column_width(0, 20)?;
 
-// fn calc_con_mod(day: Option<&Vec<SensorEntry>>) -> String {
-//     "".to_string()
-// }
-
-// fn calc_con_vig(day: Option<&Vec<SensorEntry>>) -> String {
-//     "".to_string()
-// }
-
-fn calc_total_sed(
-    day: Option<&Vec<SensorEntry>>, 
-    epoch_time: i32, 
-    cutpoint_low: i32
-) -> Result<ExcelDateTime, Box<dyn Error>> {
-    let day = match day {
-        Some(day) => day,
-        None => return Err("No data".to_string().into()),
-    };
+    for (index, summary) in event_summaries.iter().enumerate() {
+        let row = (index + 1) as u32;
+        let epochs_per_minute = 60. / epoch_time as f32;
+        let (ave_min, ave_epoch) = if summary.epochs > 0 {
+            let ave_epoch = summary.total_counts as f32 / summary.epochs as f32;
+            (ave_epoch * epochs_per_minute, ave_epoch)
+        } else {
+            (0., 0.)
+        };
 
-    let mut count = 0;
-    for entry in day.iter() {
-        if entry.value < cutpoint_low {
-            count += 1;
+        for (position, col_name) in columns.iter().enumerate() {
+            let position = position as u16;
+            match *col_name {
+                "event"             => sheet.write(row, position, translations.event(&summary.label))?,
+                "occurrences"       => sheet.write(row, position, summary.occurrences as u32)?,
+                "duration"          => sheet.write_with_format(row, position, &seconds_to_edt(summary.epochs * epoch_time)?, time_format)?,
+                "tot_counts"        => sheet.write(row, position, summary.total_counts as f64)?,
+                "ave_counts_min"    => sheet.write_with_format(row, position, ave_min, decimal_format)?,
+                "ave_counts_epoch"  => sheet.write_with_format(row, position, ave_epoch, decimal_format)?,
+                id                  => match config.bands.iter().position(|b| b.metric == id) {
+                    Some(band) => sheet.write_with_format(row, position, &seconds_to_edt(summary.band_epochs[band] * epoch_time)?, time_format)?,
+                    None => sheet.write(row, position, "Not handled!")?,
+                },
+            };
         }
     }
-    count *= epoch_time;
-    seconds_to_edt(count)
+    Ok(())
 }
 
-fn calc_total_low(
-    day: Option<&Vec<SensorEntry>>, 
-    epoch_time: i32,
-    cutpoint_low: i32,
-    cutpoint_moderate: i32,
-) -> Result<ExcelDateTime, Box<dyn Error>> {
-    let day = match day {
-        Some(day) => day,
-        None => return Err("No data".to_string().into()),
-    };
+fn write_epochs_sheet(
+    workbook: &mut Workbook,
+    rows: &[EpochRow],
+    translations: &Translations,
+    bold_format: &Format,
+    date_format: &Format,
+    time_format: &Format,
+) -> Result<(), Box<dyn Error>> {
+    let columns = [
+        "date",
+        "time",
+        "mag_value",
+        "intensity",
+        "non_wear",
+        "bout",
+        "exclusion",
+        "event",
+    ];
 
-    let mut count = 0;
-    for entry in day.iter() {
-        if entry.value >= cutpoint_low && entry.value < cutpoint_moderate{
-            count += 1;
+    // split over several sheets when the epochs don't fit on one
+    for (index, chunk) in rows.chunks(epochs::XLSX_MAX_ROWS - 1).enumerate() {
+        let sheet = workbook.add_worksheet();
+        if index == 0 {
+            sheet.set_name(translations.get("sheet.epochs"))?;
+        } else {
+            sheet.set_name(format!("{} {}", translations.get("sheet.epochs"), index + 1))?;
         }
-    }
-    count *= epoch_time;
-    seconds_to_edt(count)
-}
-
-fn calc_total_mod(
-    day: Option<&Vec<SensorEntry>>, 
-    epoch_time: i32,
-    cutpoint_moderate: i32,
-    cutpoint_vigorus: i32,
-) -> Result<ExcelDateTime, Box<dyn Error>> {
-    let day = match day {
-        Some(day) => day,
-        None => return Err("No data".to_string().into()),
-    };
 
-    let mut count = 0;
-    for entry in day.iter() {
-        if entry.value >= cutpoint_moderate && entry.value < cutpoint_vigorus{
-            count += 1;
+        for (i, column) in columns.iter().enumerate() {
+            sheet.set_column_width(i as u16, 12)?;
+            sheet.write_with_format(0, i as u16, translations.column(column, column), bold_format)?;
         }
-    }
-    count *= epoch_time;
-    seconds_to_edt(count)
-}
 
-fn calc_total_vig(day: Option<&Vec<SensorEntry>>, epoch_time: i32, cutpoint_vigorus: i32) -> Result<ExcelDateTime, Box<dyn Error>> {
-    let day = match day {
-        Some(day) => day,
-        None => return Err("No data".to_string().into()),
-    };
-
-    let mut count = 0;
-    for entry in day.iter() {
-        if entry.value >= cutpoint_vigorus {
-            count += 1;
+        for (index, epoch) in chunk.iter().enumerate() {
+            let row = (index + 1) as u32;
+            let time = ExcelDateTime::from_hms(epoch.timestamp.hour() as u16, epoch.timestamp.minute() as u8, epoch.timestamp.second() as f64)?;
+            sheet.write_with_format(row, 0, &calc_date(&epoch.timestamp.date())?, date_format)?;
+            sheet.write_with_format(row, 1, &time, time_format)?;
+            sheet.write(row, 2, epoch.value)?;
+            sheet.write(row, 3, translations.intensity(epoch.intensity))?;
+            sheet.write(row, 4, epoch.intensity == Intensity::NonWear)?;
+            if let Some(bout) = epoch.bout {
+                sheet.write(row, 5, bout as u32)?;
+            }
+            if let Some(exclusion) = epoch.exclusion {
+                sheet.write(row, 6, exclusion)?;
+            }
+            if let Some(event) = epoch.event {
+                sheet.write(row, 7, event)?;
+            }
         }
     }
-    count *= epoch_time;
-    seconds_to_edt(count)
-}
-
-fn calc_weekday(date: &NaiveDate) -> String {
-    date.format("%a").to_string()
+    Ok(())
 }
 
 fn calc_date(day: &NaiveDate) -> Result<ExcelDateTime, rust_xlsxwriter::XlsxError> {
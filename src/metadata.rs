@@ -0,0 +1,71 @@
+use crate::config::parse_datetime;
+
+use chrono::NaiveDateTime;
+
+/// One row of the metadata file. The `dog` column identifies the dog, every
+/// other column is kept as-is in file order.
+#[derive(Debug, Clone)]
+pub struct DogMetadata {
+    pub dog: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl DogMetadata {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+            .filter(|v| !v.is_empty())
+    }
+
+    pub fn get_datetime(&self, key: &str) -> Result<Option<NaiveDateTime>, String> {
+        match self.get(key) {
+            Some(v) => match parse_datetime(v) {
+                Some(d) => Ok(Some(d)),
+                None => Err(format!("Can't parse \"{}\" value \"{}\" for dog \"{}\" in the metadata file. Must be a date like 2023-05-03 14:30", key, v, self.dog)),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+/// Reads every row of the metadata CSV file. The file needs a header row with
+/// at least a `dog` column.
+pub fn load_all(path: &str) -> Result<Vec<DogMetadata>, String> {
+    let mut reader = match csv::ReaderBuilder::new().trim(csv::Trim::All).from_path(path) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Can't open metadata file {}.\nSource: {}", path, e)),
+    };
+    let headers = match reader.headers() {
+        Ok(h) => h.clone(),
+        Err(e) => return Err(format!("Can't read the header row of metadata file {}.\nSource: {}", path, e)),
+    };
+    let dog_column = match headers.iter().position(|h| h.eq_ignore_ascii_case("dog")) {
+        Some(p) => p,
+        None => return Err(format!("Metadata file {} has no \"dog\" column", path)),
+    };
+
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Can't read metadata file {}.\nSource: {}", path, e)),
+        };
+        let dog = record.get(dog_column).unwrap_or_default().to_string();
+        let fields = headers
+            .iter()
+            .zip(record.iter())
+            .enumerate()
+            .filter(|(i, _)| *i != dog_column)
+            .map(|(_, (k, v))| (k.to_string(), v.to_string()))
+            .collect();
+        rows.push(DogMetadata { dog, fields });
+    }
+    Ok(rows)
+}
+
+/// Finds the metadata row of a single dog.
+pub fn load(path: &str, dog: &str) -> Result<Option<DogMetadata>, String> {
+    Ok(load_all(path)?.into_iter().find(|m| m.dog.eq_ignore_ascii_case(dog)))
+}
@@ -0,0 +1,353 @@
+use crate::{config::Config, distribution, intensity::Band, SensorEntry};
+
+/// What a metric value measures. Durations are in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Duration,
+    Counts,
+    CountsPerMinute,
+    CountsPerEpoch,
+    Dimensionless,
+}
+
+impl Unit {
+    /// Unit of the raw values in the exports.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Unit::Duration => "seconds",
+            Unit::Counts => "counts",
+            Unit::CountsPerMinute => "counts/min",
+            Unit::CountsPerEpoch => "counts/epoch",
+            Unit::Dimensionless => "dimensionless",
+        }
+    }
+}
+
+/// How a metric value is shown in the workbook.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberFormat {
+    Time,
+    Integer,
+    Decimal,
+}
+
+/// The epochs of a single day handed to every metric.
+pub struct DayData<'a> {
+    pub entries: &'a [SensorEntry],
+    pub excluded: &'a [SensorEntry],
+}
+
+pub trait Metric {
+    /// Identifier used in config.ini and in the exports, e.g. `total_vig`.
+    fn id(&self) -> &str;
+
+    /// Column header in the workbook.
+    fn name(&self) -> &str;
+
+    fn unit(&self) -> Unit;
+
+    fn number_format(&self) -> NumberFormat {
+        match self.unit() {
+            Unit::Duration => NumberFormat::Time,
+            Unit::Counts => NumberFormat::Integer,
+            Unit::CountsPerMinute | Unit::CountsPerEpoch | Unit::Dimensionless => NumberFormat::Decimal,
+        }
+    }
+
+    /// Value of the day, `None` when it is undefined, e.g. the intensity
+    /// gradient of a day without activity. Undefined values are left blank.
+    fn compute(&self, day: &DayData, config: &Config) -> Option<f64>;
+
+    /// The intensity band whose time the metric is.
+    fn band(&self) -> Option<&Band> {
+        None
+    }
+}
+
+/// A metric backed by a plain function, used for all the built-in ones.
+pub struct FnMetric {
+    id: &'static str,
+    name: &'static str,
+    unit: Unit,
+    compute: fn(&DayData, &Config) -> Option<f64>,
+}
+
+impl Metric for FnMetric {
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    fn compute(&self, day: &DayData, config: &Config) -> Option<f64> {
+        (self.compute)(day, config)
+    }
+}
+
+/// Time of the day in one intensity band. The lowest band also counts the worn
+/// epochs below its cutpoint.
+pub struct BandMetric {
+    id: String,
+    name: String,
+    band: Band,
+    lowest: bool,
+    upper: Option<i32>,
+}
+
+impl Metric for BandMetric {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn unit(&self) -> Unit {
+        Unit::Duration
+    }
+
+    fn compute(&self, day: &DayData, config: &Config) -> Option<f64> {
+        let in_band = |v: i32| v >= 0 && (self.lowest || v >= self.band.cutpoint) && self.upper.is_none_or(|upper| v < upper);
+        Some(epochs_to_seconds(count_epochs(day, in_band), config))
+    }
+
+    fn band(&self) -> Option<&Band> {
+        Some(&self.band)
+    }
+}
+
+/// Every metric the tool knows about, in the default column order: the time in
+/// every band from the most intense one, then the rest.
+pub fn builtin_metrics(bands: &[Band]) -> Vec<Box<dyn Metric>> {
+    let metric = |id, name, unit, compute| -> Box<dyn Metric> { Box::new(FnMetric { id, name, unit, compute }) };
+    let mut metrics: Vec<Box<dyn Metric>> = vec![];
+    for (i, band) in bands.iter().enumerate().rev() {
+        metrics.push(Box::new(BandMetric {
+            id: band.metric.clone(),
+            name: band.metric_name.clone(),
+            band: band.clone(),
+            lowest: i == 0,
+            upper: bands.get(i + 1).map(|b| b.cutpoint),
+        }));
+    }
+    metrics.extend([
+        metric("t_non_zero", "T. Non-zero", Unit::Duration, calc_t_non_zero),
+        metric("t_zero", "T. Zero", Unit::Duration, calc_t_zero),
+        metric("t_empty", "T. Empty", Unit::Duration, calc_t_empty),
+        metric("t_excluded", "T. Excluded", Unit::Duration, calc_t_excluded),
+        metric("tot_counts", "Tot Counts", Unit::Counts, calc_tot_counts),
+        metric("ave_counts_min", "Ave Counts/Min", Unit::CountsPerMinute, calc_ave_counts_min),
+        metric("ave_counts_epoch", "Ave Counts/Epoch", Unit::CountsPerEpoch, calc_ave_counts_epoch),
+        metric("ig_gradient", "IG Gradient", Unit::Dimensionless, calc_ig_gradient),
+        metric("ig_intercept", "IG Intercept", Unit::Dimensionless, calc_ig_intercept),
+        metric("m60", "M60", Unit::CountsPerMinute, |day, config| calc_mx(day, 60., config)),
+        metric("m30", "M30", Unit::CountsPerMinute, |day, config| calc_mx(day, 30., config)),
+        metric("m15", "M15", Unit::CountsPerMinute, |day, config| calc_mx(day, 15., config)),
+        metric("m5", "M5", Unit::CountsPerMinute, |day, config| calc_mx(day, 5., config)),
+    ]);
+    metrics
+}
+
+/// The metrics enabled in config.ini, in the configured order.
+pub struct MetricRegistry {
+    metrics: Vec<Box<dyn Metric>>,
+}
+
+impl MetricRegistry {
+    /// Picks the metrics listed in `[metrics] enabled` (all built-in ones when
+    /// missing) and drops the ones listed in `[metrics] disabled`.
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut available = builtin_metrics(&config.bands);
+
+        for id in config.metrics_enabled.iter().flatten().chain(config.metrics_disabled.iter()) {
+            if !available.iter().any(|m| m.id() == id) {
+                return Err(format!(
+                    "Unknown metric \"{}\" in the [metrics] section of config.ini. Available metrics: {}",
+                    id,
+                    available.iter().map(|m| m.id()).collect::<Vec<_>>().join(", "),
+                ));
+            }
+        }
+
+        let mut metrics = match &config.metrics_enabled {
+            Some(enabled) => {
+                let mut metrics = vec![];
+                for id in enabled.iter() {
+                    if let Some(position) = available.iter().position(|m| m.id() == id) {
+                        metrics.push(available.remove(position));
+                    }
+                }
+                metrics
+            },
+            None => available,
+        };
+        metrics.retain(|m| !config.metrics_disabled.iter().any(|id| id == m.id()));
+
+        Ok(Self { metrics })
+    }
+
+    pub fn metrics(&self) -> &[Box<dyn Metric>] {
+        &self.metrics
+    }
+
+    /// Indices of the enabled band metrics, from the most intense band.
+    pub fn band_positions(&self) -> Vec<usize> {
+        let mut positions: Vec<usize> = (0..self.metrics.len()).filter(|i| self.metrics[*i].band().is_some()).collect();
+        positions.sort_by_key(|i| self.metrics[*i].band().map(|b| -b.cutpoint));
+        positions
+    }
+
+    /// Index of the metric in the configured order, as in the computed values.
+    pub fn position(&self, id: &str) -> Option<usize> {
+        self.metrics.iter().position(|m| m.id() == id)
+    }
+}
+
+fn epochs_to_seconds(count: usize, config: &Config) -> f64 {
+    (count as i64 * config.epoch_seconds as i64) as f64
+}
+
+fn count_epochs(day: &DayData, condition: impl Fn(i32) -> bool) -> usize {
+    day.entries.iter().filter(|e| condition(e.value)).count()
+}
+
+fn calc_t_non_zero(day: &DayData, config: &Config) -> Option<f64> {
+    Some(epochs_to_seconds(count_epochs(day, |v| v > 0), config))
+}
+
+fn calc_t_zero(day: &DayData, config: &Config) -> Option<f64> {
+    Some(epochs_to_seconds(count_epochs(day, |v| v == 0), config))
+}
+
+fn calc_t_empty(day: &DayData, config: &Config) -> Option<f64> {
+    Some(epochs_to_seconds(count_epochs(day, |v| v == -1), config))
+}
+
+fn calc_t_excluded(day: &DayData, config: &Config) -> Option<f64> {
+    Some(epochs_to_seconds(day.excluded.len(), config))
+}
+
+/// Epochs of the day with a value, without the empty (-1) ones.
+fn worn<'a>(day: &'a DayData) -> impl Iterator<Item = &'a SensorEntry> {
+    day.entries.iter().filter(|e| e.value >= 0)
+}
+
+fn calc_tot_counts(day: &DayData, _config: &Config) -> Option<f64> {
+    Some(worn(day).map(|e| e.value as i64).sum::<i64>() as f64)
+}
+
+/// Undefined on a day without worn epochs.
+fn calc_ave_counts_min(day: &DayData, config: &Config) -> Option<f64> {
+    let epochs = worn(day).count();
+    if epochs == 0 {
+        return None;
+    }
+    let minutes = epochs as f64 / (60. / config.epoch_seconds as f64);
+    Some(calc_tot_counts(day, config)? / minutes)
+}
+
+fn calc_ave_counts_epoch(day: &DayData, config: &Config) -> Option<f64> {
+    Some(calc_ave_counts_min(day, config)? / (60. / config.epoch_seconds as f64))
+}
+
+fn calc_ig_gradient(day: &DayData, config: &Config) -> Option<f64> {
+    let minutes = distribution::minutes_per_bin(day.entries, config);
+    distribution::intensity_gradient(&minutes, config.intensity_bin_width).map(|(slope, _)| slope)
+}
+
+fn calc_ig_intercept(day: &DayData, config: &Config) -> Option<f64> {
+    let minutes = distribution::minutes_per_bin(day.entries, config);
+    distribution::intensity_gradient(&minutes, config.intensity_bin_width).map(|(_, intercept)| intercept)
+}
+
+fn calc_mx(day: &DayData, minutes: f64, config: &Config) -> Option<f64> {
+    distribution::mx(day.entries, minutes, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{continuity, testing};
+
+    #[test]
+    fn filled_gaps_leave_the_totals_alone() {
+        let mut entries = testing::entries("2023-05-03 10:00", &[0, 20, 600, 1200]);
+        entries.remove(2);
+        let filled_config = testing::config("[parsing]\nfill_gaps=true");
+        let (filled, _) = continuity::check(entries, &filled_config);
+        let (unfilled, _) = continuity::check(testing::entries("2023-05-03 10:00", &[0, 20, 1200]), &testing::config(""));
+        assert_eq!(filled.len(), 4);
+
+        let metrics = builtin_metrics(&filled_config.bands);
+        let value = |entries: &[SensorEntry], id: &str| {
+            let day = DayData { entries, excluded: &[] };
+            metrics.iter().find(|m| m.id() == id).unwrap().compute(&day, &filled_config)
+        };
+        for id in ["total_sed", "total_vig", "tot_counts", "ave_counts_min"] {
+            assert_eq!(value(&filled, id), value(&unfilled, id), "{}", id);
+        }
+        assert_eq!(value(&filled, "total_sed"), Some(30.));
+        assert_eq!(value(&filled, "tot_counts"), Some(1220.));
+        assert_eq!(value(&filled, "t_empty"), Some(15.));
+    }
+
+    #[test]
+    fn empty_epochs_are_not_sedentary_or_counted() {
+        let config = testing::config("");
+        let metrics = builtin_metrics(&config.bands);
+        let entries = testing::entries("2023-05-03 10:00", &[0, -1, -1, 20, 600]);
+        let day = DayData { entries: &entries, excluded: &[] };
+        let value = |id: &str| metrics.iter().find(|m| m.id() == id).unwrap().compute(&day, &config);
+
+        // the two empty epochs used to add 30 s of sedentary time and -2 counts
+        assert_eq!(value("total_sed"), Some(30.));
+        assert_eq!(value("t_empty"), Some(30.));
+        assert_eq!(value("tot_counts"), Some(620.));
+        assert_eq!(value("ave_counts_min"), Some(620. / 0.75));
+    }
+
+    #[test]
+    fn days_without_activity_have_no_gradient_or_averages() {
+        let config = testing::config("");
+        let metrics = builtin_metrics(&config.bands);
+        let entries = testing::entries("2023-05-03 10:00", &[-1, -1, -1]);
+        let day = DayData { entries: &entries, excluded: &[] };
+        for id in ["ave_counts_min", "ave_counts_epoch", "ig_gradient", "ig_intercept", "m60", "m5"] {
+            assert_eq!(metrics.iter().find(|m| m.id() == id).unwrap().compute(&day, &config), None, "{}", id);
+        }
+        assert_eq!(metrics.iter().find(|m| m.id() == "tot_counts").unwrap().compute(&day, &config), Some(0.));
+    }
+
+    #[test]
+    fn registry_follows_enabled_and_disabled() {
+        let ids = |extra: &str| {
+            let registry = MetricRegistry::from_config(&testing::config(extra)).unwrap();
+            registry.metrics().iter().map(|m| m.id().to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids("").len(), 17);
+        assert_eq!(ids("")[..4], ["total_vig", "total_mod", "total_low", "total_sed"]);
+        assert_eq!(ids("[metrics]\nenabled=tot_counts, total_vig, m5\ndisabled=m5"), ["tot_counts", "total_vig"]);
+        assert_eq!(ids("[metrics]\ndisabled=t_excluded, m60").len(), 15);
+
+        let error = MetricRegistry::from_config(&testing::config("[metrics]\nenabled=tot_counts, steps")).err().unwrap();
+        assert!(error.contains("\"steps\"") && error.contains("tot_counts"), "{}", error);
+    }
+
+    #[test]
+    fn positions_follow_the_configured_order() {
+        let registry = MetricRegistry::from_config(&testing::config("[metrics]\nenabled=tot_counts, total_sed, total_vig")).unwrap();
+
+        assert_eq!(registry.position("total_sed"), Some(1));
+        assert_eq!(registry.position("total_mod"), None);
+        // band columns from the most intense band
+        assert_eq!(registry.band_positions(), [2, 1]);
+    }
+}
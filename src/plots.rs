@@ -0,0 +1,343 @@
+use std::{collections::HashMap, fmt::Write};
+
+use chrono::{Duration, NaiveDate, Timelike};
+
+use crate::{aggregate::Summary, config::Config, i18n::Translations, metrics::MetricRegistry, Analysis};
+
+const FONT: &str = "font-family=\"DejaVu Sans, Arial, sans-serif\" font-size=\"11\"";
+
+const ACTOGRAM_BIN_MINUTES: u32 = 10;
+const ACTOGRAM_WIDTH: f64 = 960.;
+const ACTOGRAM_ROW_HEIGHT: f64 = 24.;
+const BARS_HEIGHT: f64 = 320.;
+const BAR_WIDTH: f64 = 28.;
+const MARGIN_LEFT: f64 = 80.;
+const MARGIN_TOP: f64 = 40.;
+const MARGIN_RIGHT: f64 = 20.;
+const MARGIN_BOTTOM: f64 = 50.;
+
+/// Double-plotted actogram: every row shows a day followed by the next one,
+/// in 10 minute bins. Bar heights are mean counts per epoch, capped at the
+/// 95th percentile so single spikes don't flatten the rest. Non-wear bins are
+/// shaded grey.
+pub fn actogram_svg(analysis: &Analysis, translations: &Translations) -> String {
+    let bins_per_day = (24 * 60 / ACTOGRAM_BIN_MINUTES) as usize;
+    let mut bins: HashMap<(NaiveDate, usize), (i64, i64, bool)> = HashMap::new();
+    for entry in analysis.sensor_data.values().flatten() {
+        let bin = ((entry.time.hour() * 60 + entry.time.minute()) / ACTOGRAM_BIN_MINUTES) as usize;
+        let (counts, epochs, non_wear) = bins.entry((entry.date, bin)).or_default();
+        if entry.value < 0 {
+            *non_wear = true;
+        } else {
+            *counts += entry.value as i64;
+            *epochs += 1;
+        }
+    }
+    let mut means: Vec<f64> = bins
+        .values()
+        .filter(|(_, epochs, _)| *epochs > 0)
+        .map(|(counts, epochs, _)| *counts as f64 / *epochs as f64)
+        .filter(|mean| *mean > 0.)
+        .collect();
+    means.sort_by(|a, b| a.total_cmp(b));
+    let scale = means.get(means.len() * 95 / 100).copied().unwrap_or(1.).max(1.);
+
+    let dates = calendar_dates(analysis);
+    let plot_width = ACTOGRAM_WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let bin_width = plot_width / (2 * bins_per_day) as f64;
+    let height = MARGIN_TOP + dates.len() as f64 * ACTOGRAM_ROW_HEIGHT + MARGIN_BOTTOM;
+
+    let mut svg = header(ACTOGRAM_WIDTH, height, translations.get("plot.actogram"));
+    for (row, date) in dates.iter().enumerate() {
+        let baseline = MARGIN_TOP + (row + 1) as f64 * ACTOGRAM_ROW_HEIGHT;
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\" {}>{}</text>",
+            MARGIN_LEFT - 6.,
+            baseline - 6.,
+            FONT,
+            date.format("%d.%m.%Y"),
+        );
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{:.1}\" x2=\"{}\" y2=\"{:.1}\" stroke=\"#999\" stroke-width=\"0.5\"/>",
+            MARGIN_LEFT,
+            baseline,
+            MARGIN_LEFT + plot_width,
+            baseline,
+        );
+        // the day itself and, double plotted, the day after it
+        for (half, day) in [*date, *date + Duration::days(1)].iter().enumerate() {
+            for bin in 0..bins_per_day {
+                let (counts, epochs, non_wear) = match bins.get(&(*day, bin)) {
+                    Some(b) => *b,
+                    None => continue,
+                };
+                let x = MARGIN_LEFT + (half * bins_per_day + bin) as f64 * bin_width;
+                if non_wear && epochs == 0 {
+                    let _ = writeln!(
+                        svg,
+                        "<rect x=\"{:.2}\" y=\"{:.1}\" width=\"{:.2}\" height=\"{:.1}\" fill=\"#e6e6e6\"/>",
+                        x,
+                        baseline - ACTOGRAM_ROW_HEIGHT + 2.,
+                        bin_width,
+                        ACTOGRAM_ROW_HEIGHT - 2.,
+                    );
+                }
+                if epochs > 0 {
+                    let mean = counts as f64 / epochs as f64;
+                    let bar = (mean / scale).min(1.) * (ACTOGRAM_ROW_HEIGHT - 2.);
+                    if bar > 0. {
+                        let _ = writeln!(
+                            svg,
+                            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"#222\"/>",
+                            x,
+                            baseline - bar,
+                            bin_width,
+                            bar,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    let bottom = MARGIN_TOP + dates.len() as f64 * ACTOGRAM_ROW_HEIGHT;
+    for hour in (0..=48).step_by(6) {
+        let x = MARGIN_LEFT + hour as f64 * plot_width / 48.;
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{:.1}\" y1=\"{}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#bbb\" stroke-width=\"0.5\" stroke-dasharray=\"2,2\"/>",
+            x,
+            MARGIN_TOP,
+            x,
+            bottom,
+        );
+        let _ = writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" {}>{}</text>", x, bottom + 16., FONT, hour % 24);
+    }
+    let _ = writeln!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" {}>{}</text>",
+        MARGIN_LEFT + plot_width / 2.,
+        bottom + 36.,
+        FONT,
+        escape(translations.get("axis.hour")),
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Stacked intensity time of every analysed day, in hours.
+pub fn daily_bars_svg(summary: &Summary, metrics: &MetricRegistry, translations: &Translations) -> String {
+    // stacked from the least intense band at the bottom
+    let bands: Vec<(usize, &str, &str)> = metrics
+        .band_positions()
+        .into_iter()
+        .rev()
+        .filter_map(|index| {
+            let metric = &metrics.metrics()[index];
+            Some((index, translations.column(metric.id(), metric.name()), metric.band()?.color.as_str()))
+        })
+        .collect();
+
+    let legend_width = 120.;
+    let plot_width = (summary.days.len().max(1) as f64) * BAR_WIDTH * 1.5;
+    let width = MARGIN_LEFT + plot_width + legend_width + MARGIN_RIGHT;
+    let height = MARGIN_TOP + BARS_HEIGHT + MARGIN_BOTTOM;
+    let bottom = MARGIN_TOP + BARS_HEIGHT;
+    let hours_to_y = |hours: f64| bottom - hours / 24. * BARS_HEIGHT;
+
+    let mut svg = header(width, height, translations.get("chart.intensity"));
+    for hours in (0..=24).step_by(4) {
+        let y = hours_to_y(hours as f64);
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#ddd\" stroke-width=\"0.5\"/>",
+            MARGIN_LEFT,
+            y,
+            MARGIN_LEFT + plot_width,
+            y,
+        );
+        let _ = writeln!(svg, "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\" {}>{} h</text>", MARGIN_LEFT - 6., y + 4., FONT, hours);
+    }
+
+    for (i, day) in summary.days.iter().enumerate() {
+        let x = MARGIN_LEFT + (i as f64 * 1.5 + 0.25) * BAR_WIDTH;
+        let mut hours = 0.;
+        for (index, label, color) in bands.iter() {
+            let band = day.values[*index].unwrap_or_default() / 3600.;
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{:.1}\" y=\"{:.2}\" width=\"{}\" height=\"{:.2}\" fill=\"{}\"><title>{}: {:.0} min</title></rect>",
+                x,
+                hours_to_y(hours + band),
+                BAR_WIDTH,
+                band / 24. * BARS_HEIGHT,
+                color,
+                escape(label),
+                band * 60.,
+            );
+            hours += band;
+        }
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" {}>{}</text>",
+            x + BAR_WIDTH / 2.,
+            bottom + 16.,
+            FONT,
+            day.date.format("%d.%m."),
+        );
+    }
+
+    for (i, (_, label, color)) in bands.iter().rev().enumerate() {
+        let x = MARGIN_LEFT + plot_width + 16.;
+        let y = MARGIN_TOP + i as f64 * 18.;
+        let _ = writeln!(svg, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"12\" height=\"12\" fill=\"{}\"/>", x, y, color);
+        let _ = writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\" {}>{}</text>", x + 18., y + 10., FONT, escape(label));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Writes the plots into `[output] plots_dir` as `<dog>_actogram.svg` and
+/// `<dog>_daily_bars.svg`, or as PNG with `plots_format=png`.
+pub fn write_files(
+    analysis: &Analysis,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    translations: &Translations,
+    config: &Config,
+) -> Result<(), String> {
+    let directory = match &config.plots_dir {
+        Some(d) => d,
+        None => return Ok(()),
+    };
+    if let Err(e) = std::fs::create_dir_all(directory) {
+        return Err(format!("Can't create plots folder {}.\nSource: {}", directory, e));
+    }
+    let plots = [
+        ("actogram", actogram_svg(analysis, translations)),
+        ("daily_bars", daily_bars_svg(summary, metrics, translations)),
+    ];
+    for (name, svg) in plots.iter() {
+        let path = format!("{}/{}_{}.{}", directory.trim_end_matches('/'), config.dog, name, config.plots_format);
+        match config.plots_format.as_str() {
+            "png" => write_png(&path, svg)?,
+            _ => {
+                if let Err(e) = std::fs::write(&path, svg) {
+                    return Err(format!("Can't write plot {}.\nSource: {}", path, e));
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "png")]
+fn write_png(path: &str, svg: &str) -> Result<(), String> {
+    use resvg::{tiny_skia, usvg};
+
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = match usvg::Tree::from_str(svg, &options) {
+        Ok(t) => t,
+        Err(e) => return Err(format!("Can't render plot {}.\nSource: {}", path, e)),
+    };
+    let size = tree.size().to_int_size();
+    let mut pixmap = match tiny_skia::Pixmap::new(size.width(), size.height()) {
+        Some(p) => p,
+        None => return Err(format!("Can't render plot {}, it has no size", path)),
+    };
+    pixmap.fill(tiny_skia::Color::WHITE);
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    match pixmap.save_png(path) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Can't write plot {}.\nSource: {}", path, e)),
+    }
+}
+
+#[cfg(not(feature = "png"))]
+fn write_png(path: &str, _svg: &str) -> Result<(), String> {
+    Err(format!("Can't write {}, this build has no PNG support. Rebuild with `cargo build --release --features png`", path))
+}
+
+/// Every calendar day from the first to the last analysed one, so missing days
+/// show up as empty rows.
+fn calendar_dates(analysis: &Analysis) -> Vec<NaiveDate> {
+    let first = analysis.sensor_data.keys().min();
+    let last = analysis.sensor_data.keys().max();
+    match (first, last) {
+        (Some(first), Some(last)) => first.iter_days().take_while(|d| d <= last).collect(),
+        _ => vec![],
+    }
+}
+
+fn header(width: f64, height: f64, title: &str) -> String {
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.0} {:.0}\">",
+        width,
+        height,
+        width,
+        height,
+    );
+    let _ = writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>");
+    let _ = writeln!(
+        svg,
+        "<text x=\"{:.1}\" y=\"22\" text-anchor=\"middle\" font-family=\"DejaVu Sans, Arial, sans-serif\" font-size=\"15\" font-weight=\"bold\">{}</text>",
+        width / 2.,
+        escape(title),
+    );
+    svg
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn actograms_double_plot_every_calendar_day() {
+        let translations = Translations::from_config(&testing::config("")).unwrap();
+        let mut entries = testing::entries("2023-05-05 10:00", &[600, -1]);
+        entries.extend(testing::entries("2023-05-07 10:20", &[-1, -1]));
+        let svg = actogram_svg(&testing::analysis(entries), &translations);
+
+        // the missing day still gets a row
+        for date in ["05.05.2023", "06.05.2023", "07.05.2023"] {
+            assert!(svg.contains(&format!(">{}</text>", date)), "{}", date);
+        }
+        // the activity of the 5th is only in its own row, the non-wear of the
+        // 7th in its own row and, double plotted, in the one of the 6th
+        assert_eq!(svg.matches("fill=\"#222\"").count(), 1);
+        assert_eq!(svg.matches("fill=\"#e6e6e6\"").count(), 2);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn daily_bars_stack_the_band_time_of_every_day() {
+        let config = testing::config("[metrics]\nenabled=total_sed, total_vig, tot_counts");
+        let metrics = MetricRegistry::from_config(&config).unwrap();
+        let translations = Translations::from_config(&config).unwrap();
+        let mut entries = testing::entries("2023-05-05 10:00", &[0, 0, 0, 0, 1200]);
+        entries.extend(testing::entries("2023-05-06 10:00", &[1200, 1200, 1200, 1200]));
+        let summary = Summary::compute(&testing::analysis(entries), &metrics, &config);
+        let svg = daily_bars_svg(&summary, &metrics, &translations);
+
+        assert_eq!(svg.matches("<title>").count(), 4);
+        assert!(svg.contains("<title>Total Sed.: 1 min</title>"));
+        assert!(svg.contains("<title>Total Vig.: 0 min</title>"));
+        assert!(svg.contains(">05.05.</text>") && svg.contains(">06.05.</text>"));
+        assert!(!svg.contains("Tot Counts"));
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        assert_eq!(escape("<Walk & \"play\">"), "&lt;Walk &amp; &quot;play&quot;&gt;");
+    }
+}
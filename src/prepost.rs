@@ -0,0 +1,317 @@
+use std::error::Error;
+
+use rust_xlsxwriter::{Format, FormatBorder, Workbook, Worksheet};
+
+use crate::{
+    aggregate::{self, DayValues, Summary},
+    analyse,
+    cohort::metric_header,
+    columns::ColumnRegistry,
+    config::{Config, WindowSpec},
+    i18n::Translations,
+    metadata::DogMetadata,
+    metrics::{MetricRegistry, Unit},
+    slice, sorted_dates,
+    weeks,
+    window::AnalysisWindow,
+    Analysis,
+    WindowAnalysis,
+};
+
+const DATE_FORMAT: &str = "%d.%m.%Y";
+
+/// Which days a section of the sheet averages.
+type DayFilter = fn(&DayValues) -> bool;
+
+/// One side of the comparison.
+struct Side {
+    label: String,
+    summary: Summary,
+}
+
+/// Analyses the pre and post windows of one dog, from one recording or from
+/// `post_input_file`, and writes their averages side by side with the change.
+/// A `start` or `end` of the dog in the metadata file bounds the recording,
+/// the windows are then parts of it. Time metrics are in minutes since changes
+/// can be negative.
+pub fn write_workbook(
+    path: &str,
+    dog_metadata: Option<DogMetadata>,
+    metrics: &MetricRegistry,
+    registry: &ColumnRegistry,
+    translations: &Translations,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let bounded = dog_metadata.as_ref().is_some_and(|m| m.get("start").is_some() || m.get("end").is_some());
+    let sides = if bounded {
+        if config.post_input_file.is_some() {
+            return Err("Can't use \"post_input_file\" attribute in the [prepost] section of config.ini with a dog whose start or end is in the metadata file".into());
+        }
+        println!("Analysing the pre and post windows of {}", config.input_file);
+        let analysis = analyse(&mut config.clone(), dog_metadata, translations)?;
+        within(&analysis, metrics, translations, config)
+    } else {
+        let mut post_config = config.with_window(&config.post_window);
+        if let Some(file) = &config.post_input_file {
+            post_config.input_file = file.clone();
+            post_config.input_file_sheet = config.post_input_file_sheet.clone().unwrap_or(config.input_file_sheet.clone());
+        }
+        [
+            side(&mut config.with_window(&config.pre_window), &config.pre_window, dog_metadata.clone(), metrics, translations)?,
+            side(&mut post_config, &config.post_window, dog_metadata, metrics, translations)?,
+        ]
+    };
+
+    let bold_format = Format::new().set_bold().set_border(FormatBorder::Hair);
+    let decimal_format = Format::new().set_num_format(&config.decimals_format);
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.prepost"))?;
+
+    let columns: Vec<usize> = registry.weekly_columns().iter().filter_map(|id| metrics.position(id)).collect();
+    let sections: [(&str, DayFilter); 3] = [
+        ("label.all_days", |_| true),
+        ("label.weekend", |d| weeks::is_weekend(&d.date)),
+        ("label.weekdays", |d| !weeks::is_weekend(&d.date)),
+    ];
+
+    let mut row = 0;
+    for (title, filter) in sections.iter() {
+        sheet.write_with_format(row, 0, translations.get(title), &bold_format)?;
+        row += 1;
+        write_headers(sheet, row, &columns, metrics, translations, &bold_format)?;
+
+        let mut side_averages = vec![];
+        for side in sides.iter() {
+            row += 1;
+            let days: Vec<&DayValues> = side.summary.days.iter().filter(|d| filter(d)).collect();
+            let values = averages(&side.summary, &columns, metrics, *filter);
+            write_average_row(sheet, row, &side.label, &days, &values, &decimal_format)?;
+            side_averages.push(values);
+        }
+        row = write_change_rows(sheet, row, "", &side_averages[0], &side_averages[1], translations, &decimal_format)?;
+        row += 2;
+    }
+
+    workbook.save(path)?;
+    Ok(())
+}
+
+fn side(
+    config: &mut Config,
+    window: &WindowSpec,
+    dog_metadata: Option<DogMetadata>,
+    metrics: &MetricRegistry,
+    translations: &Translations,
+) -> Result<Side, String> {
+    println!("Analysing the {} window of {}", window.name, config.input_file);
+    let analysis = analyse(config, dog_metadata, translations)?;
+    Ok(Side {
+        label: translations.get(&format!("label.{}", window.name)).to_string(),
+        summary: Summary::compute(&analysis, metrics, config),
+    })
+}
+
+/// The pre and post windows as parts of one analysis, their days counted from
+/// its first day like the named `[windows]`.
+fn within(analysis: &Analysis, metrics: &MetricRegistry, translations: &Translations, config: &Config) -> [Side; 2] {
+    let first_day = sorted_dates(&analysis.sensor_data).first().copied().unwrap_or_default();
+    [&config.pre_window, &config.post_window].map(|window| Side {
+        label: translations.get(&format!("label.{}", window.name)).to_string(),
+        summary: Summary::compute(&slice(analysis, AnalysisWindow::of(window, first_day)), metrics, config),
+    })
+}
+
+/// Comparison of the `[windows]` of a recording: the averages of every window
+/// and their change from the first one.
+pub fn write_windows_sheet(
+    workbook: &mut Workbook,
+    windows: &[WindowAnalysis],
+    metrics: &MetricRegistry,
+    registry: &ColumnRegistry,
+    translations: &Translations,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let bold_format = Format::new().set_bold().set_border(FormatBorder::Hair);
+    let decimal_format = Format::new().set_num_format(&config.decimals_format);
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(translations.get("sheet.windows"))?;
+
+    let columns: Vec<usize> = registry.weekly_columns().iter().filter_map(|id| metrics.position(id)).collect();
+    write_headers(sheet, 0, &columns, metrics, translations, &bold_format)?;
+    let mut row = 0;
+    let mut window_averages = vec![];
+    for window in windows.iter() {
+        row += 1;
+        let days: Vec<&DayValues> = window.summary.days.iter().collect();
+        let values = averages(&window.summary, &columns, metrics, |_| true);
+        write_average_row(sheet, row, &window.name, &days, &values, &decimal_format)?;
+        window_averages.push(values);
+    }
+    for (i, window) in windows.iter().enumerate().skip(1) {
+        let prefix = format!("{} – {}: ", window.name, windows[0].name);
+        row = write_change_rows(sheet, row, &prefix, &window_averages[0], &window_averages[i], translations, &decimal_format)?;
+    }
+    Ok(())
+}
+
+/// Averages of the days of `summary` matching `filter`, time metrics in minutes.
+fn averages(summary: &Summary, columns: &[usize], metrics: &MetricRegistry, filter: DayFilter) -> Vec<Option<f64>> {
+    columns
+        .iter()
+        .map(|index| {
+            let mean = aggregate::mean(&summary.days, *index, filter)?;
+            match metrics.metrics()[*index].unit() {
+                Unit::Duration => Some(mean / 60.),
+                _ => Some(mean),
+            }
+        })
+        .collect()
+}
+
+/// Row with the number and dates of the averaged days and their averages.
+fn write_average_row(
+    sheet: &mut Worksheet,
+    row: u32,
+    label: &str,
+    days: &[&DayValues],
+    values: &[Option<f64>],
+    decimal_format: &Format,
+) -> Result<(), Box<dyn Error>> {
+    sheet.write(row, 0, label)?;
+    sheet.write(row, 1, days.len() as u32)?;
+    if let (Some(first), Some(last)) = (days.first(), days.last()) {
+        sheet.write(row, 2, format!("{} – {}", first.date.format(DATE_FORMAT), last.date.format(DATE_FORMAT)))?;
+    }
+    write_values(sheet, row, values, decimal_format)
+}
+
+/// Change and relative change from `from` to `to` below `row`, returns the last
+/// row written.
+fn write_change_rows(
+    sheet: &mut Worksheet,
+    mut row: u32,
+    prefix: &str,
+    from: &[Option<f64>],
+    to: &[Option<f64>],
+    translations: &Translations,
+    decimal_format: &Format,
+) -> Result<u32, Box<dyn Error>> {
+    let (changes, percentages) = changes(from, to);
+    for (label, values) in [("label.change", changes), ("label.change_pct", percentages)] {
+        row += 1;
+        sheet.write(row, 0, format!("{}{}", prefix, translations.get(label)))?;
+        write_values(sheet, row, &values, decimal_format)?;
+    }
+    Ok(row)
+}
+
+/// Change and relative change in percent of every value, the latter undefined
+/// when `from` is zero.
+fn changes(from: &[Option<f64>], to: &[Option<f64>]) -> (Vec<Option<f64>>, Vec<Option<f64>>) {
+    let changes: Vec<Option<f64>> = from.iter().zip(to.iter()).map(|(from, to)| Some(to.as_ref()? - from.as_ref()?)).collect();
+    let percentages: Vec<Option<f64>> = from
+        .iter()
+        .zip(changes.iter())
+        .map(|(from, change)| match (from, change) {
+            (Some(from), Some(change)) if *from != 0. => Some(change / from * 100.),
+            _ => None,
+        })
+        .collect();
+    (changes, percentages)
+}
+
+fn write_headers(
+    sheet: &mut Worksheet,
+    row: u32,
+    columns: &[usize],
+    metrics: &MetricRegistry,
+    translations: &Translations,
+    bold_format: &Format,
+) -> Result<(), Box<dyn Error>> {
+    let mut headers = vec![
+        translations.get("column.label").to_string(),
+        translations.get("column.days").to_string(),
+        translations.get("column.dates").to_string(),
+    ];
+    headers.extend(columns.iter().map(|index| metric_header(metrics, translations, *index)));
+    for (i, header) in headers.iter().enumerate() {
+        sheet.set_column_width(i as u16, 14)?;
+        sheet.write_with_format(row, i as u16, header, bold_format)?;
+    }
+    sheet.set_column_width(2, 24)?;
+    Ok(())
+}
+
+fn write_values(sheet: &mut Worksheet, row: u32, values: &[Option<f64>], format: &Format) -> Result<(), Box<dyn Error>> {
+    for (i, value) in values.iter().enumerate() {
+        if let Some(value) = value {
+            sheet.write_with_format(row, 3 + i as u16, *value, format)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{config::parse_datetime, testing};
+
+    #[test]
+    fn windows_are_parts_of_the_metadata_window() {
+        let mut entries = vec![];
+        for (day, value) in [("2023-05-02", 1), ("2023-05-03", 10), ("2023-05-04", 10), ("2023-05-05", 100), ("2023-05-06", 100)] {
+            entries.extend(testing::entries(&format!("{} 10:00", day), &[value]));
+        }
+        let metadata = DogMetadata {
+            dog: "billy".to_string(),
+            fields: vec![("start".to_string(), "2023-05-03 00:00".to_string()), ("end".to_string(), "2023-05-07 00:00".to_string())],
+        };
+        let window = AnalysisWindow { start: metadata.get_datetime("start").unwrap(), end: parse_datetime("2023-05-07 00:00") };
+        let analysis = Analysis {
+            metadata: Some(metadata),
+            window,
+            exclusions: vec![],
+            sensor_data: testing::by_day(entries.into_iter().filter(|e| window.contains(e.timestamp())).collect()),
+            excluded_data: HashMap::new(),
+            quality_issues: vec![],
+            bouts: vec![],
+            event_summaries: None,
+        };
+        let config = testing::config("[prepost]\npre_skip_days_num=0\npre_day_window_size=2\npost_skip_days_num=2\npost_day_window_size=2");
+        let metrics = MetricRegistry::from_config(&config).unwrap();
+        let translations = Translations::from_config(&config).unwrap();
+
+        let [pre, post] = within(&analysis, &metrics, &translations, &config);
+        let counts = metrics.position("tot_counts").unwrap();
+        let days = |side: &Side| side.summary.days.iter().map(|d| (d.date.to_string(), d.values[counts])).collect::<Vec<_>>();
+        assert_eq!(days(&pre), [("2023-05-03".to_string(), Some(10.)), ("2023-05-04".to_string(), Some(10.))]);
+        assert_eq!(days(&post), [("2023-05-05".to_string(), Some(100.)), ("2023-05-06".to_string(), Some(100.))]);
+    }
+
+    #[test]
+    fn changes_are_from_the_pre_to_the_post_averages() {
+        let config = testing::config("[metrics]\nenabled=total_mod, tot_counts, ig_gradient");
+        let metrics = MetricRegistry::from_config(&config).unwrap();
+        let summary = |days: &[(&str, &[i32])]| {
+            let entries = days.iter().flat_map(|(day, values)| testing::entries(&format!("{} 10:00", day), values)).collect();
+            Summary::compute(&testing::analysis(entries), &metrics, &config)
+        };
+        let pre = summary(&[("2023-05-05", &[600, 600, 600, 600]), ("2023-05-06", &[0, -1])]);
+        let post = summary(&[("2023-05-12", &[600, 600]), ("2023-05-13", &[1200, -1])]);
+
+        // time in minutes, the gradient is undefined on all of the days
+        let columns = [0, 1, 2];
+        let pre = averages(&pre, &columns, &metrics, |_| true);
+        let post = averages(&post, &columns, &metrics, |_| true);
+        assert_eq!(pre, [Some(0.5), Some(1200.), None]);
+        assert_eq!(post, [Some(0.25), Some(1200.), None]);
+
+        let (change, percentage) = changes(&pre, &post);
+        assert_eq!(change, [Some(-0.25), Some(0.), None]);
+        assert_eq!(percentage, [Some(-50.), Some(0.), None]);
+        assert_eq!(changes(&[Some(0.)], &[Some(2.)]), (vec![Some(2.)], vec![None]));
+    }
+}
@@ -0,0 +1,272 @@
+use std::{collections::BTreeMap, fs::File, io::BufWriter};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+use crate::{aggregate::Summary, config::Config, metrics::MetricRegistry, Analysis};
+
+/// Bumped on every change to the document layout, see docs/json_report.md.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Serialize)]
+struct Report<'a> {
+    schema: &'static str,
+    schema_version: u32,
+    generator: String,
+    dog: Dog<'a>,
+    config: ConfigUsed<'a>,
+    metrics: Vec<MetricInfo<'a>>,
+    days: Vec<Day>,
+    weekly: Vec<Period>,
+    bouts: Vec<Bout<'a>>,
+    quality: Vec<Issue<'a>>,
+    events: Option<Vec<Event<'a>>>,
+}
+
+#[derive(Serialize)]
+struct Dog<'a> {
+    id: &'a str,
+    metadata: BTreeMap<&'a str, &'a str>,
+}
+
+#[derive(Serialize)]
+struct ConfigUsed<'a> {
+    input_file: &'a str,
+    epoch_seconds: i32,
+    bands: Vec<BandInfo<'a>>,
+    window_start: Option<String>,
+    window_end: Option<String>,
+    skip_days_num: i32,
+    day_window_size: i32,
+    week_start: String,
+    exclusions: Vec<Exclusion<'a>>,
+    bout_band: &'a str,
+    bout_min_minutes: f64,
+    bout_tolerance_epochs: usize,
+}
+
+#[derive(Serialize)]
+struct BandInfo<'a> {
+    id: &'a str,
+    label: &'a str,
+    cutpoint: i32,
+    metric: &'a str,
+}
+
+#[derive(Serialize)]
+struct Exclusion<'a> {
+    start: String,
+    end: String,
+    reason: &'a str,
+}
+
+#[derive(Serialize)]
+struct MetricInfo<'a> {
+    id: &'a str,
+    name: &'a str,
+    unit: &'static str,
+}
+
+#[derive(Serialize)]
+struct Day {
+    day: usize,
+    date: String,
+    weekday: String,
+    values: BTreeMap<String, Option<f64>>,
+}
+
+#[derive(Serialize)]
+struct Period {
+    level: &'static str,
+    week: usize,
+    dates: Vec<String>,
+    values: BTreeMap<String, Option<f64>>,
+}
+
+#[derive(Serialize)]
+struct Bout<'a> {
+    start: String,
+    end: String,
+    duration_seconds: i64,
+    epochs: usize,
+    band_epochs: BTreeMap<&'a str, usize>,
+    total_counts: i64,
+}
+
+#[derive(Serialize)]
+struct Issue<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    timestamp: String,
+    detail: &'a str,
+    action: &'a str,
+}
+
+#[derive(Serialize)]
+struct Event<'a> {
+    label: &'a str,
+    occurrences: usize,
+    epochs: i32,
+    total_counts: i64,
+    band_epochs: BTreeMap<&'a str, i32>,
+}
+
+pub fn write_json(
+    path: &str,
+    analysis: &Analysis,
+    summary: &Summary,
+    metrics: &MetricRegistry,
+    config: &Config,
+) -> Result<(), String> {
+    let report = build(analysis, summary, metrics, config);
+    let file = match File::create(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Can't create JSON report {}.\nSource: {}", path, e)),
+    };
+    match serde_json::to_writer_pretty(BufWriter::new(file), &report) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Can't write JSON report {}.\nSource: {}", path, e)),
+    }
+}
+
+fn build<'a>(analysis: &'a Analysis, summary: &Summary, metrics: &'a MetricRegistry, config: &'a Config) -> Report<'a> {
+    let values = |values: &[Option<f64>]| -> BTreeMap<String, Option<f64>> {
+        metrics.metrics().iter().map(|m| m.id().to_string()).zip(values.iter().copied()).collect()
+    };
+    let band_ids = || config.bands.iter().map(|b| b.id.as_str());
+
+    Report {
+        schema: "dog_accelerometer_report",
+        schema_version: SCHEMA_VERSION,
+        generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        dog: Dog {
+            id: &config.dog,
+            metadata: analysis
+                .metadata
+                .iter()
+                .flat_map(|m| m.fields.iter())
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect(),
+        },
+        config: ConfigUsed {
+            input_file: &config.input_file,
+            epoch_seconds: config.epoch_seconds,
+            bands: config
+                .bands
+                .iter()
+                .map(|b| BandInfo { id: &b.id, label: &b.label, cutpoint: b.cutpoint, metric: &b.metric })
+                .collect(),
+            window_start: analysis.window.start.map(datetime),
+            window_end: analysis.window.end.map(datetime),
+            skip_days_num: config.skip_days_num,
+            day_window_size: config.day_window_size,
+            week_start: config.week_start.to_string(),
+            exclusions: analysis
+                .exclusions
+                .iter()
+                .map(|e| Exclusion { start: datetime(e.start), end: datetime(e.end), reason: &e.reason })
+                .collect(),
+            bout_band: &config.bands[config.bout_band].id,
+            bout_min_minutes: config.bout_min_minutes,
+            bout_tolerance_epochs: config.bout_tolerance_epochs,
+        },
+        metrics: metrics
+            .metrics()
+            .iter()
+            .map(|m| MetricInfo { id: m.id(), name: m.name(), unit: m.unit().label() })
+            .collect(),
+        days: summary
+            .days
+            .iter()
+            .map(|d| Day {
+                day: d.number,
+                date: date(d.date),
+                weekday: d.date.weekday().to_string(),
+                values: values(&d.values),
+            })
+            .collect(),
+        weekly: summary
+            .periods
+            .iter()
+            .map(|p| Period {
+                level: p.kind.id(),
+                week: p.number,
+                dates: p.days.iter().map(|d| date(d.date)).collect(),
+                values: values(&p.values),
+            })
+            .collect(),
+        bouts: analysis
+            .bouts
+            .iter()
+            .map(|b| Bout {
+                start: datetime(b.start),
+                end: datetime(b.end),
+                duration_seconds: (b.end - b.start).num_seconds(),
+                epochs: b.epochs,
+                band_epochs: band_ids().zip(b.band_epochs.iter().copied()).collect(),
+                total_counts: b.total_counts,
+            })
+            .collect(),
+        quality: analysis
+            .quality_issues
+            .iter()
+            .map(|i| Issue {
+                kind: i.kind.id(),
+                timestamp: datetime(i.timestamp),
+                detail: &i.detail,
+                action: &i.action,
+            })
+            .collect(),
+        events: analysis.event_summaries.as_ref().map(|summaries| {
+            summaries
+                .iter()
+                .map(|s| Event {
+                    label: &s.label,
+                    occurrences: s.occurrences,
+                    epochs: s.epochs,
+                    total_counts: s.total_counts,
+                    band_epochs: band_ids().zip(s.band_epochs.iter().copied()).collect(),
+                })
+                .collect()
+        }),
+    }
+}
+
+fn date(date: NaiveDate) -> String {
+    date.format(DATE_FORMAT).to_string()
+}
+
+fn datetime(datetime: NaiveDateTime) -> String {
+    datetime.format(DATETIME_FORMAT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn reports_follow_the_documented_layout() {
+        let config = testing::config("[metrics]\nenabled=total_mod, ig_gradient");
+        let metrics = MetricRegistry::from_config(&config).unwrap();
+        let mut entries = testing::entries("2023-05-06 10:00", &[600, 700]);
+        entries.extend(testing::entries("2023-05-08 10:00", &[-1]));
+        let analysis = testing::analysis(entries);
+        let summary = Summary::compute(&analysis, &metrics, &config);
+
+        let report = serde_json::to_value(build(&analysis, &summary, &metrics, &config)).unwrap();
+        assert_eq!(report["schema_version"], 1);
+        assert_eq!(report["config"]["bands"].as_array().unwrap().len(), 4);
+        assert_eq!(report["config"]["bands"][2]["metric"], "total_mod");
+        assert_eq!(report["metrics"][1]["unit"], "dimensionless");
+        assert_eq!(report["days"].as_array().unwrap().len(), 2);
+        assert_eq!(report["days"][0]["weekday"], "Sat");
+        assert_eq!(report["days"][0]["values"]["total_mod"], 30.);
+        assert!(report["days"][1]["values"]["ig_gradient"].is_null());
+        assert_eq!(report["weekly"][0]["dates"], serde_json::json!(["2023-05-06"]));
+        assert!(report["events"].is_null());
+    }
+}
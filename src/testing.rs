@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::{config::{parse_datetime, Config}, window::AnalysisWindow, Analysis, SensorEntry};
+
+/// The required keys of config.ini, with 15 second epochs.
+pub const CONFIG: &str = "
+[general]
+input_file=./data/test.xlsx
+input_file_sheet=Test
+output_file=./data/test_summary.xlsx
+[format]
+decimals=0.00
+date=dd-mm-yyyy
+time=hh:mm:ss
+weekend_color=CC0000
+week_color=E5FFCC
+[parsing]
+skip_days_num=0
+day_window_size=14
+epoch_seconds=15
+cutpoint_low=50
+cutpoint_moderate=500
+cutpoint_vigorus=1000
+";
+
+/// Test config, `extra` config.ini lines add or override keys.
+pub fn config(extra: &str) -> Config {
+    Config::read(&format!("{}\n{}", CONFIG, extra)).unwrap()
+}
+
+/// Epochs 15 seconds apart from `start`, one per value.
+pub fn entries(start: &str, values: &[i32]) -> Vec<SensorEntry> {
+    let start = parse_datetime(start).unwrap();
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let mut entry = SensorEntry::empty(start + Duration::seconds(15 * i as i64));
+            entry.value = *value;
+            entry
+        })
+        .collect()
+}
+
+pub fn by_day(entries: Vec<SensorEntry>) -> HashMap<NaiveDate, Vec<SensorEntry>> {
+    let mut days: HashMap<NaiveDate, Vec<SensorEntry>> = HashMap::new();
+    for entry in entries.into_iter() {
+        days.entry(entry.date).or_default().push(entry);
+    }
+    days
+}
+
+/// Analysis of the epochs alone, without metadata, exclusions or events.
+pub fn analysis(entries: Vec<SensorEntry>) -> Analysis {
+    Analysis {
+        metadata: None,
+        window: AnalysisWindow::default(),
+        exclusions: vec![],
+        sensor_data: by_day(entries),
+        excluded_data: HashMap::new(),
+        quality_issues: vec![],
+        bouts: vec![],
+        event_summaries: None,
+    }
+}
@@ -0,0 +1,56 @@
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+pub fn is_weekend(date: &NaiveDate) -> bool {
+    date.weekday() == Weekday::Sat || date.weekday() == Weekday::Sun
+}
+
+/// First day of the calendar week `date` belongs to. With `Weekday::Mon` this
+/// matches ISO weeks.
+pub fn week_start_of(date: &NaiveDate, week_start: Weekday) -> NaiveDate {
+    let offset = date.weekday().days_since(week_start);
+    *date - Duration::days(offset.into())
+}
+
+/// Groups sorted dates into calendar weeks. Weeks at the start and end of the
+/// recording, or with missing days, are kept with fewer than 7 days.
+pub fn group_by_week(dates: &[NaiveDate], week_start: Weekday) -> Vec<Vec<NaiveDate>> {
+    let mut weeks: Vec<Vec<NaiveDate>> = vec![];
+    let mut current_start = None;
+    for date in dates.iter() {
+        let start = week_start_of(date, week_start);
+        if current_start != Some(start) {
+            current_start = Some(start);
+            weeks.push(vec![]);
+        }
+        if let Some(week) = weeks.last_mut() {
+            week.push(*date);
+        }
+    }
+    weeks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 5, day).unwrap()
+    }
+
+    #[test]
+    fn weeks_start_on_the_configured_day() {
+        // 3 May 2023 was a Wednesday
+        assert_eq!(week_start_of(&date(3), Weekday::Mon), date(1));
+        assert_eq!(week_start_of(&date(3), Weekday::Sun), date(7) - Duration::days(7));
+        assert_eq!(week_start_of(&date(8), Weekday::Mon), date(8));
+        assert!(is_weekend(&date(6)) && is_weekend(&date(7)) && !is_weekend(&date(8)));
+    }
+
+    #[test]
+    fn partial_weeks_are_kept() {
+        let dates: Vec<NaiveDate> = [3, 4, 5, 6, 7, 8, 9, 16].into_iter().map(date).collect();
+
+        let weeks = group_by_week(&dates, Weekday::Mon);
+        assert_eq!(weeks, [dates[..5].to_vec(), dates[5..7].to_vec(), dates[7..].to_vec()]);
+    }
+}
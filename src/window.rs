@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::{config::{Config, WindowSpec}, SensorEntry};
+
+/// Bounds of the analysed part of a recording. `start` is inclusive and `end`
+/// exclusive, a missing bound falls back to `skip_days_num`/`day_window_size`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnalysisWindow {
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+}
+
+impl AnalysisWindow {
+    /// Bounds of a named window: its start and end dates, or its days counted
+    /// from `first_day`, the first analysed day.
+    pub fn of(spec: &WindowSpec, first_day: NaiveDate) -> Self {
+        let day = |days: i32| first_day.and_time(NaiveTime::MIN) + Duration::days(days.into());
+        Self {
+            start: spec.start_datetime.or(Some(day(spec.skip_days_num))),
+            end: spec.end_datetime.or(Some(day(spec.skip_days_num + spec.day_window_size))),
+        }
+    }
+
+    pub fn contains(&self, timestamp: NaiveDateTime) -> bool {
+        self.start.is_none_or(|start| timestamp >= start) && self.end.is_none_or(|end| timestamp < end)
+    }
+}
+
+/// Groups the entries inside the analysis window by day.
+pub fn apply(
+    entries: Vec<SensorEntry>,
+    window: &AnalysisWindow,
+    config: &Config,
+) -> HashMap<NaiveDate, Vec<SensorEntry>> {
+    let mut first_seen_date = None;
+    let mut first_parsed_date = None;
+    let mut sensor_data: HashMap<NaiveDate, Vec<SensorEntry>> = HashMap::new();
+
+    for sensor_entry in entries.into_iter() {
+        if !window.contains(sensor_entry.timestamp()) {
+            continue;
+        }
+
+        if first_seen_date.is_none() {
+            first_seen_date = Some(sensor_entry.date)
+        }
+        let first_seen_date_value = first_seen_date.unwrap();
+
+        // check if still skipping first X days
+        if window.start.is_none() && sensor_entry.date.signed_duration_since(first_seen_date_value).num_days() < config.skip_days_num.into() {
+            continue;
+        }
+
+        if first_parsed_date.is_none() {
+            first_parsed_date = Some(sensor_entry.date)
+        }
+        let first_parsed_date_value = first_parsed_date.unwrap();
+
+        // check if all required dates parsed
+        if window.end.is_none() && sensor_entry.date.signed_duration_since(first_parsed_date_value).num_days() >= config.day_window_size.into() {
+            continue;
+        }
+
+        let entry = sensor_data.entry(sensor_entry.date).or_default();
+        entry.push(sensor_entry);
+    }
+    sensor_data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{parse_datetime, WindowSpec};
+    use super::*;
+
+    #[test]
+    fn windows_count_days_from_the_first_analysed_day() {
+        let first_day = NaiveDate::from_ymd_opt(2023, 5, 3).unwrap();
+        let window = AnalysisWindow::of(&WindowSpec::parse("treatment", "2-3").unwrap(), first_day);
+        assert_eq!(window.start, parse_datetime("2023-05-04 00:00"));
+        assert_eq!(window.end, parse_datetime("2023-05-06 00:00"));
+        assert!(window.contains(parse_datetime("2023-05-05 23:59").unwrap()));
+        assert!(!window.contains(parse_datetime("2023-05-06 00:00").unwrap()));
+
+        let dates = WindowSpec::parse("visit", "2023-05-20 08:00, 2023-05-22 20:00").unwrap();
+        assert_eq!(AnalysisWindow::of(&dates, first_day).start, parse_datetime("2023-05-20 08:00"));
+    }
+}